@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::mock::StepRng;
+
+use chip8_rs::settings::Settings;
+use chip8_rs::vm::VM;
+
+// a second of emulated time
+const TIMER_TICKS: u32 = 60;
+
+fn run_roms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run_headless");
+
+    for rom in ["fabada.ch8", "poker.ch8"] {
+        for ips in [1_000, 10_000, 100_000] {
+            group.bench_with_input(BenchmarkId::new(rom, ips), &ips, |b, ips| {
+                let overrides = Settings {
+                    ips: Some(*ips),
+                    ..Settings::default()
+                };
+                let mut vm = VM::new(StepRng::new(1, 1), overrides, Settings::default());
+                vm.load_rom(PathBuf::from("roms").join(rom)).unwrap();
+
+                b.iter(|| vm.run_headless(TIMER_TICKS).unwrap());
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, run_roms);
+criterion_main!(benches);
@@ -1,68 +1,459 @@
-use crate::error::Error;
+use crate::config::{Config, TIMER_HZ};
+use crate::error::{to_sdl_err, Error};
+use crate::wav::WavWriter;
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use serde::{Deserialize, Serialize};
 use std::f32::consts::TAU;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread::{self, JoinHandle};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-const NOTE_FREQ: f32 = 349.23; // G4
+pub const DEFAULT_TONE: f32 = 349.23; // G4
 const BASE_VOLUME: f32 = 0.1;
+// relative to BASE_VOLUME
+pub const DEFAULT_VOLUME: f32 = 1.0;
+// attack and release time of the buzzer, in seconds, so it doesn't click
+const ENVELOPE_TIME: f32 = 0.005;
+// the most buzzing queued ahead of the audio device, in timer ticks
+const MAX_QUEUED_TICKS: u32 = 3;
+// small enough to follow the sound timer closely
+const BUFFER_SAMPLES: u16 = 512;
+// for recordings made without an audio device
+const HEADLESS_SAMPLE_RATE: u32 = 44_100;
+// buffers of samples passed between the audio callback and the thread
+// writing a recording, so the writer can fall that many callbacks behind
+const RECORDING_BUFFERS: usize = 32;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    Square,
+    #[default]
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    /// The value of the wave at `phase`, from 0.0 to 1.0, between -1.0 and 1.0
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Self::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Self::Sine => (phase * TAU).sin(),
+            Self::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Self::Square),
+            "sine" => Ok(Self::Sine),
+            "triangle" => Ok(Self::Triangle),
+            _ => Err(format!("unknown waveform: {} (square, sine, triangle)", s)),
+        }
+    }
+}
 
 pub struct Audio {
-    device: AudioDevice<Wave>,
+    device: AudioDevice<Buzzer>,
+    // where the buzzer is being recorded to, and the thread writing the file
+    recording: Option<(PathBuf, JoinHandle<io::Result<()>>)>,
 }
 
 impl Audio {
-    pub fn new(context: &sdl2::Sdl, volume: f32) -> Result<Self> {
-        let device = build_audio_device(context, volume)?;
-        Ok(Self { device })
+    pub fn new(context: &sdl2::Sdl, config: &Config) -> Result<Self> {
+        let device = build_audio_device(context, config)?;
+        // the wave is silent while the buzzer is off
+        device.resume();
+        Ok(Self {
+            device,
+            recording: None,
+        })
+    }
+
+    /// Queues the timer ticks the buzzer sounded for since the last call,
+    /// and whether it is still sounding
+    pub fn buzz(&mut self, sound_ticks: u32, is_buzzing: bool) {
+        self.device.lock().wave.buzz(sound_ticks, is_buzzing);
+    }
+
+    pub fn set_muted(&mut self, is_muted: bool) {
+        self.device.lock().is_muted = is_muted;
     }
 
-    pub fn set_status(&mut self, is_playing: bool) {
-        if is_playing {
-            self.device.resume();
-        } else {
-            self.device.pause();
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Starts writing everything the buzzer plays, silence included, to a
+    /// WAV file. The audio callback copies the samples into buffers allocated
+    /// up front, and sends them to a thread that writes them and sends the
+    /// buffers back, so it never allocates or waits for the disk.
+    pub fn start_recording(&mut self, path: PathBuf) -> Result<()> {
+        let spec = self.device.spec();
+        let mut writer = WavWriter::create(&path, spec.freq as u32)?;
+
+        let (filled, received) = mpsc::sync_channel::<Vec<f32>>(RECORDING_BUFFERS);
+        let (returned, empty) = mpsc::sync_channel(RECORDING_BUFFERS);
+        let size = spec.samples as usize * spec.channels as usize;
+        for _ in 0..RECORDING_BUFFERS {
+            // never blocks, as the channel has room for every buffer
+            let _ = returned.send(Vec::with_capacity(size));
         }
+        let thread = thread::spawn(move || {
+            for samples in received {
+                writer.write(&samples)?;
+                let _ = returned.send(samples);
+            }
+            writer.finish()
+        });
+
+        self.device.lock().recording = Some(RecordingBuffers { filled, empty });
+        self.recording = Some((path, thread));
+        Ok(())
     }
+
+    /// Stops recording, returning the path of the recording if there was one
+    pub fn stop_recording(&mut self) -> Result<Option<PathBuf>> {
+        // the writer thread finishes the file once the callback lets go of
+        // the channel
+        self.device.lock().recording = None;
+
+        match self.recording.take() {
+            Some((path, thread)) => {
+                thread.join().map_err(|_| {
+                    Error::SystemError("The audio recording thread panicked".to_string())
+                })??;
+                Ok(Some(path))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// the state of the audio callback
+struct Buzzer {
+    wave: Wave,
+    // shared with the thread writing the recording
+    recording: Option<RecordingBuffers>,
+    is_muted: bool,
 }
 
-struct Wave {
+// the buffers of samples going to the thread writing a recording, and
+// coming back empty
+struct RecordingBuffers {
+    filled: SyncSender<Vec<f32>>,
+    empty: Receiver<Vec<f32>>,
+}
+
+impl RecordingBuffers {
+    /// Passes a copy of `samples` on to the writer thread, without
+    /// allocating, returning false once the thread has stopped
+    fn send(&self, samples: &[f32]) -> bool {
+        let mut buffer = match self.empty.try_recv() {
+            Ok(buffer) => buffer,
+            // the writer is too far behind, so these samples are lost
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => return false,
+        };
+        buffer.clear();
+        buffer.extend_from_slice(samples);
+        self.filled.try_send(buffer).is_ok()
+    }
+}
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
+
+    fn callback(&mut self, output: &mut [Self::Channel]) {
+        self.wave.fill(output);
+
+        if let Some(recording) = &self.recording {
+            // the writer thread stops on errors, which stop_recording reports
+            if !recording.send(output) {
+                self.recording = None;
+            }
+        }
+
+        // recordings keep the sound while muted
+        if self.is_muted {
+            output.fill(0.0);
+        }
+    }
+}
+
+/// Records the buzzer without an audio device, a timer tick at a time
+pub struct HeadlessRecorder<W: Write + Seek> {
+    wave: Wave,
+    writer: WavWriter<W>,
+    samples: Vec<f32>,
+}
+
+impl HeadlessRecorder<BufWriter<File>> {
+    pub fn create(path: &Path, config: &Config) -> Result<Self> {
+        let writer = WavWriter::create(path, HEADLESS_SAMPLE_RATE)?;
+        Ok(Self::new(writer, config))
+    }
+}
+
+impl<W: Write + Seek> HeadlessRecorder<W> {
+    fn new(writer: WavWriter<W>, config: &Config) -> Self {
+        let wave = Wave::new(config, HEADLESS_SAMPLE_RATE);
+        let samples = vec![0.0; wave.samples_per_tick as usize];
+        Self {
+            wave,
+            writer,
+            samples,
+        }
+    }
+
+    /// Records a timer tick, with the buzzer sounding or not
+    pub fn record_tick(&mut self, is_buzzing: bool) -> Result<()> {
+        self.wave.buzz(is_buzzing as u32, false);
+        self.wave.fill(&mut self.samples);
+        self.writer.write(&self.samples)?;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Generates the buzzer sound, sample by sample, from the ticks of the sound
+/// timer, so that even the shortest beeps last exactly as long as they should
+pub struct Wave {
+    waveform: Waveform,
     phase_inc: f32,
     phase: f32,
     volume: f32,
+    samples_per_tick: u32,
+    // samples left to play for the sound ticks already run
+    queued: u32,
+    // whether the sound timer was still running at the last update, to keep
+    // sounding if the queue runs out before the next one
+    is_sustained: bool,
+    // envelope level, from 0.0 to 1.0, and its change per sample
+    level: f32,
+    level_step: f32,
 }
 
-impl AudioCallback for Wave {
-    type Channel = f32;
+impl Wave {
+    pub fn new(config: &Config, sample_rate: u32) -> Self {
+        Self {
+            waveform: config.waveform,
+            phase_inc: config.tone / sample_rate as f32,
+            phase: 0.0,
+            volume: BASE_VOLUME * config.volume,
+            samples_per_tick: (sample_rate as f64 / TIMER_HZ).round() as u32,
+            queued: 0,
+            is_sustained: false,
+            level: 0.0,
+            level_step: 1.0 / (ENVELOPE_TIME * sample_rate as f32).max(1.0),
+        }
+    }
 
-    fn callback(&mut self, output: &mut [Self::Channel]) {
-        // sine wave
+    /// Queues the timer ticks the buzzer sounded for, and whether it is still
+    /// sounding
+    pub fn buzz(&mut self, sound_ticks: u32, is_buzzing: bool) {
+        self.queued = (self.queued + sound_ticks * self.samples_per_tick)
+            .min(MAX_QUEUED_TICKS * self.samples_per_tick);
+        self.is_sustained = is_buzzing;
+    }
+
+    pub fn fill(&mut self, output: &mut [f32]) {
         for x in output.iter_mut() {
-            *x = (self.phase * TAU).sin() * self.volume;
+            let is_on = self.queued > 0 || self.is_sustained;
+            self.queued = self.queued.saturating_sub(1);
+
+            self.level = if is_on {
+                (self.level + self.level_step).min(1.0)
+            } else {
+                (self.level - self.level_step).max(0.0)
+            };
+            if self.level == 0.0 {
+                // every beep starts at the same point of the wave
+                self.phase = 0.0;
+                *x = 0.0;
+                continue;
+            }
+
+            *x = self.waveform.sample(self.phase) * self.volume * self.level;
             self.phase = (self.phase + self.phase_inc) % 1.0;
         }
     }
 }
 
-fn build_audio_device(context: &sdl2::Sdl, volume: f32) -> Result<AudioDevice<Wave>> {
+fn build_audio_device(context: &sdl2::Sdl, config: &Config) -> Result<AudioDevice<Buzzer>> {
     let audio_subsystem = context.audio().map_err(to_sdl_err)?;
     let spec = AudioSpecDesired {
         freq: None,
         channels: Some(1),
-        samples: None,
+        samples: Some(BUFFER_SAMPLES),
     };
 
     let device = audio_subsystem
-        .open_playback(None, &spec, |spec| Wave {
-            phase_inc: NOTE_FREQ / spec.freq as f32,
-            phase: 0.0,
-            volume: BASE_VOLUME * volume,
+        .open_playback(None, &spec, |spec| Buzzer {
+            wave: Wave::new(config, spec.freq as u32),
+            recording: None,
+            is_muted: false,
         })
         .map_err(|_| Error::SystemError("Error initilizating audio".to_string()))?;
 
     Ok(device)
 }
 
-fn to_sdl_err(err: String) -> Error {
-    Error::SystemError(err)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_waveform_samples() {
+        assert_eq!(Waveform::Square.sample(0.25), 1.0);
+        assert_eq!(Waveform::Square.sample(0.75), -1.0);
+        assert_eq!(Waveform::Sine.sample(0.0), 0.0);
+        assert_eq!(Waveform::Triangle.sample(0.0), -1.0);
+        assert_eq!(Waveform::Triangle.sample(0.5), 1.0);
+        assert_eq!(Waveform::Triangle.sample(0.25), 0.0);
+    }
+
+    // 10 samples per timer tick, and an envelope of 3 samples
+    const SAMPLE_RATE: u32 = 600;
+    const TICK: usize = 10;
+    const ENVELOPE: usize = 3;
+
+    fn any_square_wave() -> Wave {
+        let config = Config {
+            waveform: Waveform::Square,
+            ..Config::default()
+        };
+        Wave::new(&config, SAMPLE_RATE)
+    }
+
+    #[test]
+    fn test_wave_is_silent_while_the_buzzer_is_off() {
+        let mut wave = any_square_wave();
+        let mut output = [1.0; 64];
+
+        wave.fill(&mut output);
+
+        assert_eq!(output, [0.0; 64]);
+    }
+
+    #[test]
+    fn test_wave_plays_the_sound_ticks() {
+        let mut wave = any_square_wave();
+        let mut output = [0.0; 64];
+
+        wave.buzz(1, false);
+        wave.fill(&mut output);
+
+        assert!(output[..TICK].iter().all(|x| *x != 0.0));
+        assert!(output[TICK + ENVELOPE..].iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn test_wave_fades_in_and_out() {
+        let mut wave = any_square_wave();
+        let mut output = [0.0; 64];
+
+        wave.buzz(1, false);
+        wave.fill(&mut output);
+
+        assert!(output[0].abs() < output[ENVELOPE].abs());
+        assert!(output[TICK + 1].abs() < output[TICK - 1].abs());
+    }
+
+    #[test]
+    fn test_wave_keeps_playing_while_sustained() {
+        let mut wave = any_square_wave();
+        let mut output = [0.0; 64];
+
+        wave.buzz(1, true);
+        wave.fill(&mut output);
+        assert!(output.iter().skip(1).all(|x| *x != 0.0));
+
+        wave.buzz(0, false);
+        wave.fill(&mut output);
+        assert!(output[ENVELOPE..].iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn test_wave_caps_the_queue() {
+        let mut wave = any_square_wave();
+        let mut output = [0.0; 64];
+
+        wave.buzz(100, false);
+        wave.fill(&mut output);
+
+        let max_samples = MAX_QUEUED_TICKS as usize * TICK + ENVELOPE;
+        assert!(output[max_samples..].iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn test_headless_recorder_records_a_tick_at_a_time() {
+        let mut bytes = Cursor::new(vec![]);
+        let writer = WavWriter::new(&mut bytes, HEADLESS_SAMPLE_RATE).unwrap();
+        let mut recorder = HeadlessRecorder::new(writer, &Config::default());
+
+        for is_buzzing in [false, true, false, false] {
+            recorder.record_tick(is_buzzing).unwrap();
+        }
+
+        let tick = recorder.samples.len();
+        recorder.finish().unwrap();
+        let bytes = bytes.into_inner();
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(samples.len(), 4 * tick, "Silence is recorded too");
+        assert!(samples[..tick].iter().all(|x| *x == 0));
+        assert!(samples[tick..2 * tick].iter().any(|x| *x != 0));
+        assert!(samples[3 * tick..].iter().all(|x| *x == 0));
+    }
+
+    #[test]
+    fn test_recording_buffers_are_passed_back_and_forth() {
+        let (filled, received) = mpsc::sync_channel(1);
+        let (returned, empty) = mpsc::sync_channel(1);
+        returned.send(Vec::with_capacity(4)).unwrap();
+        let buffers = RecordingBuffers { filled, empty };
+
+        assert!(buffers.send(&[0.5, -0.5]));
+        let samples = received.try_recv().unwrap();
+        assert_eq!(samples, [0.5, -0.5]);
+        // with no buffer to fill until the writer returns one
+        assert!(buffers.send(&[1.0]));
+        assert!(received.try_recv().is_err());
+
+        returned.send(samples).unwrap();
+        assert!(buffers.send(&[1.0]));
+        assert_eq!(received.try_recv().unwrap(), [1.0]);
+
+        drop((returned, received));
+        assert!(!buffers.send(&[1.0]));
+    }
+
+    #[test]
+    fn test_waveform_from_str() {
+        assert_eq!("square".parse(), Ok(Waveform::Square));
+        assert!("noise".parse::<Waveform>().is_err());
+    }
 }
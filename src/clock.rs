@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant};
+
+use crate::config::TIMER_HZ;
+
+// elapsed time is capped, so the emulation doesn't try to catch up after
+// being stalled (i.e. while the window is dragged)
+const MAX_ELAPSED: Duration = Duration::from_millis(250);
+const STATS_PERIOD: Duration = Duration::from_secs(1);
+
+/// Keeps the emulation in step with real time: the CHIP-8 timers tick at
+/// 60 Hz however often frames are drawn, with the instructions spread over
+/// the timer ticks.
+pub struct Clock {
+    // instructions per timer tick, which may not be a whole number
+    cycles_per_tick: f64,
+    pending_time: Duration,
+    pending_cycles: f64,
+}
+
+impl Clock {
+    pub fn new(ips: u32) -> Self {
+        Self {
+            cycles_per_tick: ips as f64 / TIMER_HZ,
+            pending_time: Duration::ZERO,
+            pending_cycles: 0.0,
+        }
+    }
+
+    fn tick_period() -> Duration {
+        Duration::from_nanos((1e9 / TIMER_HZ) as u64)
+    }
+
+    /// Returns the timer ticks due after `elapsed` time. Frames drawn less
+    /// often than 60 Hz skip ticks, and frames drawn more often repeat them.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.pending_time += elapsed.min(MAX_ELAPSED);
+
+        let mut ticks = 0;
+        while self.pending_time >= Self::tick_period() {
+            self.pending_time -= Self::tick_period();
+            ticks += 1;
+        }
+
+        ticks
+    }
+
+    /// The time left until the next timer tick is due, as of the last
+    /// `advance`
+    pub fn until_next_tick(&self) -> Duration {
+        Self::tick_period().saturating_sub(self.pending_time)
+    }
+
+    /// Returns the instructions to run before the next timer tick
+    pub fn next_cycles(&mut self) -> u32 {
+        self.pending_cycles += self.cycles_per_tick;
+        let cycles = self.pending_cycles.floor();
+        self.pending_cycles -= cycles;
+
+        cycles as u32
+    }
+}
+
+/// Measures the actual frames and instructions per second
+pub struct Stats {
+    since: Instant,
+    frames: u32,
+    cycles: u64,
+    pub fps: f64,
+    pub ips: f64,
+}
+
+impl Stats {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            since: now,
+            frames: 0,
+            cycles: 0,
+            fps: 0.0,
+            ips: 0.0,
+        }
+    }
+
+    /// Counts a frame drawn after running `cycles` instructions
+    pub fn record_frame(&mut self, cycles: u32) {
+        self.frames += 1;
+        self.cycles += cycles as u64;
+    }
+
+    /// Recomputes the rates once per period, returning whether they changed
+    pub fn update(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.since);
+        if elapsed < STATS_PERIOD {
+            return false;
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        self.fps = self.frames as f64 / seconds;
+        self.ips = self.cycles as f64 / seconds;
+
+        self.since = now;
+        self.frames = 0;
+        self.cycles = 0;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_advance_ticks_timers_at_60_hz() {
+        let mut clock = Clock::new(600);
+
+        let ticks: u32 = (0..60).map(|_| clock.advance(ms(1000) / 60)).sum();
+
+        assert!((59..=60).contains(&ticks));
+    }
+
+    #[test]
+    fn test_advance_repeats_frames_when_drawing_faster() {
+        let mut clock = Clock::new(600);
+
+        // 120 Hz frames
+        let ticks: Vec<u32> = (0..4).map(|_| clock.advance(ms(9))).collect();
+
+        assert_eq!(ticks, [0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_advance_skips_frames_when_drawing_slower() {
+        let mut clock = Clock::new(600);
+
+        // 20 Hz frames
+        assert_eq!(clock.advance(ms(50)), 3);
+    }
+
+    #[test]
+    fn test_advance_caps_elapsed_time() {
+        let mut clock = Clock::new(600);
+        assert_eq!(clock.advance(Duration::from_secs(5)), 15);
+    }
+
+    #[test]
+    fn test_until_next_tick() {
+        let mut clock = Clock::new(600);
+
+        clock.advance(ms(20));
+
+        assert_eq!(clock.until_next_tick(), Clock::tick_period() * 2 - ms(20));
+    }
+
+    #[test]
+    fn test_next_cycles_spreads_instructions_over_ticks() {
+        // 1.5 instructions per tick
+        let mut clock = Clock::new(90);
+
+        let cycles: Vec<u32> = (0..4).map(|_| clock.next_cycles()).collect();
+
+        assert_eq!(cycles, [1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn test_stats_update_once_per_period() {
+        let start = Instant::now();
+        let mut stats = Stats::new(start);
+
+        for _ in 0..30 {
+            stats.record_frame(20);
+        }
+
+        assert!(!stats.update(start + ms(500)));
+        assert!(stats.update(start + ms(1000)));
+        assert_eq!(stats.fps, 30.0);
+        assert_eq!(stats.ips, 600.0);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::audio::{self, Waveform};
+use crate::error::Error;
+use crate::filters::Filter;
+use crate::palette::Palette;
+use crate::phosphor::Persistence;
+use crate::screen;
+use crate::settings::Settings;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub const DEFAULT_FPS: f64 = 60.0;
+// the CHIP-8 timers tick at 60 Hz, and the tickrates of the CHIP-8 database
+// count the instructions run per timer tick
+pub const TIMER_HZ: f64 = 60.0;
+// instructions per timer tick for ROMs missing from the CHIP-8 database
+const DEFAULT_TICKRATE: u32 = 1;
+const DEFAULT_FAST_FORWARD: u32 = 4;
+
+/// The display, speed and audio settings of a run, with the defaults filled
+/// in for the ones missing from [`Settings`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Config {
+    pub window_size: (u32, u32),
+    pub fullscreen: bool,
+    // scale the screen by whole numbers only
+    pub integer_scale: bool,
+    pub fps: f64,
+    // instructions per second
+    pub ips: u32,
+    // run the CPU on its own thread
+    pub threaded: bool,
+    // how many times faster to run while fast-forwarding
+    pub fast_forward: u32,
+    // show the frames and instructions per second over the screen
+    pub show_fps: bool,
+    pub volume: f32,
+    // buzzer frequency, in Hz
+    pub tone: f32,
+    pub waveform: Waveform,
+    pub palette: Palette,
+    pub persistence: Persistence,
+    pub filter: Filter,
+    pub rumble: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window_size: screen::window_size(screen::DEFAULT_SCALE).expect("Invalid default scale"),
+            fullscreen: false,
+            integer_scale: false,
+            fps: DEFAULT_FPS,
+            ips: DEFAULT_TICKRATE * TIMER_HZ as u32,
+            threaded: false,
+            fast_forward: DEFAULT_FAST_FORWARD,
+            show_fps: false,
+            volume: audio::DEFAULT_VOLUME,
+            tone: audio::DEFAULT_TONE,
+            waveform: Waveform::default(),
+            palette: Palette::default(),
+            persistence: Persistence::default(),
+            filter: Filter::default(),
+            rumble: false,
+        }
+    }
+}
+
+impl TryFrom<&Settings> for Config {
+    type Error = Error;
+
+    fn try_from(settings: &Settings) -> Result<Self> {
+        let defaults = Self::default();
+
+        let ips = match (settings.ips, settings.tickrate) {
+            (Some(ips), _) => ips,
+            (None, Some(tickrate)) => tickrate
+                .checked_mul(TIMER_HZ as u32)
+                .ok_or_else(|| Error::ConfigError(format!("Tickrate too large: {}", tickrate)))?,
+            (None, None) => defaults.ips,
+        };
+
+        let scale = positive_or(settings.scale, screen::DEFAULT_SCALE)?;
+        let window_size = screen::window_size(scale)
+            .ok_or_else(|| Error::ConfigError(format!("Scale too large: {}", scale)))?;
+
+        Ok(Self {
+            window_size,
+            fullscreen: settings.fullscreen.unwrap_or(defaults.fullscreen),
+            integer_scale: settings.integer_scale.unwrap_or(defaults.integer_scale),
+            fps: positive_or(settings.fps, defaults.fps)?,
+            ips: ips.max(1),
+            threaded: settings.threaded.unwrap_or(defaults.threaded),
+            fast_forward: positive_or(settings.fast_forward, defaults.fast_forward)?,
+            show_fps: settings.show_fps.unwrap_or(defaults.show_fps),
+            volume: settings.volume.unwrap_or(defaults.volume).max(0.0),
+            tone: positive_or(settings.tone, defaults.tone)?,
+            waveform: settings.waveform.unwrap_or(defaults.waveform),
+            palette: Palette::find(settings.palette.as_ref(), &settings.palettes)?,
+            persistence: settings.persistence.unwrap_or(defaults.persistence),
+            filter: settings.filter.unwrap_or(defaults.filter),
+            rumble: settings.rumble.unwrap_or(defaults.rumble),
+        })
+    }
+}
+
+// where NaN and infinity aren't positive numbers either
+fn positive_or<T: Into<f64> + Copy + std::fmt::Display>(value: Option<T>, default: T) -> Result<T> {
+    match value {
+        Some(x) if !x.into().is_finite() || x.into() <= 0.0 => Err(Error::ConfigError(format!(
+            "Expected a positive number, got {}",
+            x
+        ))),
+        Some(x) => Ok(x),
+        None => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::PaletteSetting;
+
+    #[test]
+    fn test_from_empty_settings() {
+        let config = Config::try_from(&Settings::default()).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_from_settings() {
+        let settings = Settings {
+            scale: Some(4),
+            fps: Some(30.0),
+            tickrate: Some(10),
+            waveform: Some(Waveform::Square),
+            palette: Some(PaletteSetting::Name("amber".to_string())),
+            ..Settings::default()
+        };
+
+        let config = Config::try_from(&settings).unwrap();
+
+        assert_eq!(config.window_size, (256, 128));
+        assert_eq!(config.fps, 30.0);
+        assert_eq!(config.ips, 600);
+        assert_eq!(config.waveform, Waveform::Square);
+        assert_eq!(config.palette.name, "amber");
+    }
+
+    #[test]
+    fn test_ips_takes_precedence_over_tickrate() {
+        let settings = Settings {
+            ips: Some(1000),
+            tickrate: Some(10),
+            ..Settings::default()
+        };
+
+        assert_eq!(Config::try_from(&settings).unwrap().ips, 1000);
+    }
+
+    #[test]
+    fn test_from_settings_returns_err_on_invalid_values() {
+        let settings = Settings {
+            fps: Some(0.0),
+            ..Settings::default()
+        };
+        assert!(matches!(
+            Config::try_from(&settings),
+            Err(Error::ConfigError(_))
+        ));
+
+        for settings in [
+            Settings {
+                fps: Some(f64::NAN),
+                ..Settings::default()
+            },
+            Settings {
+                tone: Some(f32::INFINITY),
+                ..Settings::default()
+            },
+            Settings {
+                scale: Some(0),
+                ..Settings::default()
+            },
+            Settings {
+                scale: Some(u32::MAX),
+                ..Settings::default()
+            },
+            Settings {
+                tickrate: Some(u32::MAX),
+                ..Settings::default()
+            },
+        ] {
+            assert!(matches!(
+                Config::try_from(&settings),
+                Err(Error::ConfigError(_))
+            ));
+        }
+
+        let settings = Settings {
+            palette: Some(PaletteSetting::Name("not-a-palette".to_string())),
+            ..Settings::default()
+        };
+        assert!(matches!(
+            Config::try_from(&settings),
+            Err(Error::ConfigError(_))
+        ));
+    }
+}
@@ -1,67 +1,99 @@
 use rand::{Rng, RngCore};
 
 use crate::error::CPUError;
-use crate::instruction::Instruction;
+use crate::framebuffer::Framebuffer;
+use crate::instruction::{Instruction, Key, VReg};
+use crate::quirks::Quirks;
 use crate::sprites;
 
 pub type Result<T> = std::result::Result<T, CPUError>;
 
-const MEM_SIZE: usize = 4096;
+pub const MEM_SIZE: usize = 4096;
 const MEM_END: usize = 0xFFF;
 const MEM_START: usize = 0x200;
+pub const MAX_ROM_SIZE: usize = MEM_SIZE - MEM_START;
 const BASE_DIGIT_ADDRESS: usize = 0x0;
 const V_REGISTERS_SIZE: usize = 16;
-const SCREEN_WIDTH: usize = 64;
-const SCREEN_HEIGHT: usize = 32;
 const STACK_SIZE: usize = 16;
 const KEYMAP_SIZE: usize = 16;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
 pub struct TickStatus {
     pub is_waiting_for_key: bool,
     pub is_buzzing: bool,
 }
 
-impl Default for TickStatus {
-    fn default() -> Self {
-        Self {
-            is_waiting_for_key: false,
-            is_buzzing: false,
-        }
-    }
+/// A copy of the machine state, for debug views
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    pub v_registers: [u8; V_REGISTERS_SIZE],
+    pub i_register: u16,
+    pub pc: u16,
+    // how many addresses are on the stack
+    pub sp: usize,
+    pub stack: [u16; STACK_SIZE],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keypad: [bool; KEYMAP_SIZE],
+    pub memory: Box<[u8; MEM_SIZE]>,
+    pub quirks: Quirks,
+}
+
+/// When `Fx0A` considers a key to have been entered
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum KeyWait {
+    #[default]
+    Press,
+    // like the COSMAC VIP: a key must be pressed and then released
+    Release,
 }
 
-#[allow(dead_code)]
-pub struct CPU<'a> {
+#[allow(dead_code, clippy::upper_case_acronyms)]
+pub struct CPU {
     memory: [u8; MEM_SIZE],
     pc: u16,
     sp: usize,
     v_registers: [u8; V_REGISTERS_SIZE],
     i_register: u16,
-    v_buffer: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    // instructions already decoded, by address. Writes to memory invalidate
+    // the ones they overlap, so self-modifying code still works.
+    decoded: Box<[Option<Instruction>; MEM_SIZE]>,
+    v_buffer: Framebuffer,
+    // bumped whenever the screen may have changed
+    screen_generation: u64,
     stack: [u16; STACK_SIZE],
-    rng: &'a mut dyn RngCore,
+    rng: Box<dyn RngCore + Send>,
     keypad: [bool; KEYMAP_SIZE],
     delay_timer: u8,
     sound_timer: u8,
-    is_waiting_for_key: (bool, usize),
+    is_waiting_for_key: (bool, VReg),
+    key_pressed_while_waiting: Option<Key>,
+    key_wait: KeyWait,
+    is_waiting_for_vblank: bool,
+    quirks: Quirks,
 }
 
-impl<'a> CPU<'a> {
-    pub fn new(rng: &'a mut impl RngCore) -> Self {
+impl CPU {
+    pub fn new(rng: impl RngCore + Send + 'static) -> Self {
         let mut cpu = Self {
             memory: [0; MEM_SIZE],
             pc: 0x200,
             sp: 0,
             v_registers: [0; V_REGISTERS_SIZE],
             i_register: 0,
-            v_buffer: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            decoded: Box::new([None; MEM_SIZE]),
+            v_buffer: Framebuffer::default(),
+            screen_generation: 0,
             stack: [0; STACK_SIZE],
-            rng: rng,
+            rng: Box::new(rng),
             keypad: [false; KEYMAP_SIZE],
             delay_timer: 0,
             sound_timer: 0,
-            is_waiting_for_key: (false, 0x0),
+            is_waiting_for_key: (false, VReg::new(0x0)),
+            key_pressed_while_waiting: None,
+            key_wait: KeyWait::default(),
+            is_waiting_for_vblank: false,
+            quirks: Quirks::default(),
         };
 
         cpu.load_private_data();
@@ -69,11 +101,12 @@ impl<'a> CPU<'a> {
     }
 
     pub fn load_rom(&mut self, rom: &[u8]) -> Result<()> {
-        if rom.len() > (MEM_SIZE - MEM_START) {
+        if rom.len() > MAX_ROM_SIZE {
             return Err(CPUError::MemoryOverflow);
         }
 
         self.memory[MEM_START..(MEM_START + rom.len())].copy_from_slice(rom);
+        self.decoded.fill(None);
         Ok(())
     }
 
@@ -83,38 +116,68 @@ impl<'a> CPU<'a> {
         self.sp = 0;
         self.v_registers = [0; V_REGISTERS_SIZE];
         self.i_register = 0;
-        self.v_buffer = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.decoded.fill(None);
+        self.v_buffer.clear();
+        self.touch_screen();
         self.stack = [0; STACK_SIZE];
         self.keypad = [false; KEYMAP_SIZE];
         self.delay_timer = 0;
         self.sound_timer = 0;
-        self.is_waiting_for_key = (false, 0x0);
+        self.is_waiting_for_key = (false, VReg::new(0x0));
+        self.key_pressed_while_waiting = None;
+        self.is_waiting_for_vblank = false;
 
         self.load_private_data();
     }
 
-    pub fn set_key_status(&mut self, i: usize, status: bool) -> Result<()> {
-        let key = self
-            .keypad
-            .get_mut(i as usize)
-            .ok_or(CPUError::InvalidKey(i))?;
-        *key = status;
+    pub fn set_key_status(&mut self, key: Key, status: bool) {
+        self.keypad[key.index()] = status;
 
         let (is_waiting, vx) = self.is_waiting_for_key;
+        if !is_waiting {
+            return;
+        }
+
+        let is_key_entered = match (self.key_wait, status) {
+            (KeyWait::Press, true) => true,
+            (KeyWait::Release, true) => {
+                self.key_pressed_while_waiting = Some(key);
+                false
+            }
+            (KeyWait::Release, false) => self.key_pressed_while_waiting == Some(key),
+            (KeyWait::Press, false) => false,
+        };
 
-        if is_waiting && status {
-            self.set_register(vx as u8, i as u8)?;
-            self.is_waiting_for_key = (false, 0x00);
+        if is_key_entered {
+            self.set_register(vx, key.index() as u8);
+            self.is_waiting_for_key = (false, VReg::new(0x0));
+            self.key_pressed_while_waiting = None;
         }
+    }
 
-        Ok(())
+    pub fn set_key_wait(&mut self, key_wait: KeyWait) {
+        self.key_wait = key_wait;
     }
 
-    pub fn tick(&mut self) -> Result<TickStatus> {
-        // update internal timers
-        // TODO: decouple 1 cpu tick = 1 decrement
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Decrements the delay and sound timers. Meant to be called at 60Hz.
+    pub fn tick_timers(&mut self) {
         self.delay_timer = self.delay_timer.saturating_sub(1);
         self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.is_waiting_for_vblank = false;
+    }
+
+    pub fn tick(&mut self) -> Result<TickStatus> {
+        // skip execution of instructions until the next frame after drawing
+        if self.is_waiting_for_vblank {
+            return Ok(TickStatus {
+                is_buzzing: self.sound_timer > 0,
+                ..TickStatus::default()
+            });
+        }
 
         // skip execution of instructions if we are waiting for a key press
         let (is_waiting, _) = self.is_waiting_for_key;
@@ -125,15 +188,14 @@ impl<'a> CPU<'a> {
             });
         }
 
-        let opcode = (self.read_byte()? as u16) << 8 | self.read_byte()? as u16;
-        let instruction = Instruction::try_from(opcode)?;
+        let instruction = self.fetch()?;
 
         let mut status = match instruction {
-            Instruction::NoOp => Ok(TickStatus::default()),
+            Instruction::NoOp => TickStatus::default(),
             Instruction::ClearScreen => self.exec_clear_screen(),
-            Instruction::Return => self.exec_return(),
+            Instruction::Return => self.exec_return()?,
             Instruction::Jump(addr) => self.exec_jump(addr),
-            Instruction::Call(addr) => self.exec_call(addr),
+            Instruction::Call(addr) => self.exec_call(addr)?,
             Instruction::SkipVxEqual(x, value) => self.exec_skip_vx_if_equal(x, value),
             Instruction::SkipVxNotEqual(x, value) => self.exec_skip_vx_if_not_equal(x, value),
             Instruction::SkipEqual(x, y) => self.exec_skip_if_equal(x, y),
@@ -145,35 +207,64 @@ impl<'a> CPU<'a> {
             Instruction::Xor(x, y) => self.exec_xor(x, y),
             Instruction::Add(x, y) => self.exec_add(x, y),
             Instruction::Sub(x, y) => self.exec_sub(x, y),
-            Instruction::ShiftRightVx(x) => self.exec_shiftr_vx(x),
+            Instruction::ShiftRightVx(x, y) => self.exec_shiftr_vx(x, y),
             Instruction::SubN(x, y) => self.exec_subn(x, y),
-            Instruction::ShiftLeftVx(x) => self.exec_shiftl_vx(x),
+            Instruction::ShiftLeftVx(x, y) => self.exec_shiftl_vx(x, y),
             Instruction::SkipNotEqual(x, y) => self.exec_skip_if_not_equal(x, y),
             Instruction::LoadI(x) => self.exec_load_i(x),
             Instruction::JumpOffset(x, addr) => self.exec_jump_offset(x, addr),
             Instruction::Rand(x, value) => self.exec_rand(x, value),
-            Instruction::DrawSprite(x, y, n) => self.exec_draw_sprite(x, y, n),
-            Instruction::SkipIfKey(vx) => self.exec_skip_if_key(vx),
-            Instruction::SkipIfNotKey(vx) => self.exec_skip_if_not_key(vx),
+            Instruction::DrawSprite(x, y, n) => self.exec_draw_sprite(x, y, n)?,
+            Instruction::SkipIfKey(vx) => self.exec_skip_if_key(vx)?,
+            Instruction::SkipIfNotKey(vx) => self.exec_skip_if_not_key(vx)?,
             Instruction::LoadDelay(vx) => self.exec_load_delay(vx),
             Instruction::WaitForKey(vx) => self.exec_wait_for_key(vx),
             Instruction::SetDelay(vx) => self.exec_set_delay(vx),
             Instruction::SetSound(vx) => self.exec_set_sound(vx),
             Instruction::AddToIndex(vx) => self.exec_add_to_index(vx),
-            Instruction::LoadDigit(vx) => self.exec_load_digit(vx),
-            Instruction::LoadBCD(vx) => self.exec_load_bcd(vx),
-            Instruction::LoadMem(vx) => self.exec_load_mem(vx),
-            Instruction::SaveMem(vx) => self.exec_save_mem(vx),
-        }?;
+            Instruction::LoadDigit(vx) => self.exec_load_digit(vx)?,
+            Instruction::LoadBCD(vx) => self.exec_load_bcd(vx)?,
+            Instruction::LoadMem(vx) => self.exec_load_mem(vx)?,
+            Instruction::SaveMem(vx) => self.exec_save_mem(vx)?,
+        };
 
         status.is_buzzing = self.sound_timer > 0;
         Ok(status)
     }
 
-    pub fn visual_buffer(&self) -> &[bool; SCREEN_WIDTH * SCREEN_HEIGHT] {
+    pub fn is_buzzing(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    pub fn visual_buffer(&self) -> &Framebuffer {
         &self.v_buffer
     }
 
+    /// A counter that changes every time the screen is cleared or drawn to,
+    /// so callers can skip redrawing an unchanged screen
+    pub fn screen_generation(&self) -> u64 {
+        self.screen_generation
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            v_registers: self.v_registers,
+            i_register: self.i_register,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            keypad: self.keypad,
+            memory: Box::new(self.memory),
+            quirks: self.quirks,
+        }
+    }
+
+    fn touch_screen(&mut self) {
+        self.screen_generation = self.screen_generation.wrapping_add(1);
+    }
+
     fn load_private_data(&mut self) {
         for x in 0..=0xF {
             let digit = sprites::digit_sprite_data(x).unwrap();
@@ -182,6 +273,20 @@ impl<'a> CPU<'a> {
         }
     }
 
+    fn fetch(&mut self) -> Result<Instruction> {
+        let addr = self.pc as usize;
+        if let Some(instruction) = self.decoded.get(addr).copied().flatten() {
+            self.pc += 2;
+            return Ok(instruction);
+        }
+
+        let opcode = (self.read_byte()? as u16) << 8 | self.read_byte()? as u16;
+        let instruction = Instruction::try_from(opcode)?;
+        self.decoded[addr] = Some(instruction);
+
+        Ok(instruction)
+    }
+
     fn read_byte(&mut self) -> Result<u8> {
         let value = self
             .memory
@@ -191,20 +296,12 @@ impl<'a> CPU<'a> {
         Ok(*value)
     }
 
-    fn read_register(&self, x: u8) -> Result<u8> {
-        self.v_registers
-            .get(x as usize)
-            .ok_or(CPUError::InvalidVRegister(x))
-            .copied()
+    fn read_register(&self, x: VReg) -> u8 {
+        self.v_registers[x.index()]
     }
 
-    fn set_register(&mut self, x: u8, value: u8) -> Result<()> {
-        let i = self
-            .v_registers
-            .get_mut(x as usize)
-            .ok_or(CPUError::InvalidVRegister(x))?;
-        *i = value;
-        Ok(())
+    fn set_register(&mut self, x: VReg, value: u8) {
+        self.v_registers[x.index()] = value;
     }
 
     fn set_memory(&mut self, addr: u16, value: u8) -> Result<()> {
@@ -214,6 +311,9 @@ impl<'a> CPU<'a> {
         }
 
         self.memory[addr as usize] = value;
+        // the byte is part of the instructions starting at it and before it
+        self.decoded[addr as usize] = None;
+        self.decoded[addr as usize - 1] = None;
         Ok(())
     }
 
@@ -231,7 +331,7 @@ impl<'a> CPU<'a> {
         let mut x = value;
 
         if x as usize > MEM_END {
-            x = x & (MEM_END as u16);
+            x &= MEM_END as u16;
             carry = 0x01;
         }
 
@@ -239,11 +339,8 @@ impl<'a> CPU<'a> {
         carry
     }
 
-    fn read_key(&self, i: u8) -> Result<bool> {
-        self.keypad
-            .get(i as usize)
-            .ok_or(CPUError::InvalidKey(i as usize))
-            .copied()
+    fn read_key(&self, key: Key) -> bool {
+        self.keypad[key.index()]
     }
 
     fn push_stack(&mut self, value: u16) -> Result<()> {
@@ -276,9 +373,10 @@ impl<'a> CPU<'a> {
         Ok(addr as u16)
     }
 
-    fn exec_clear_screen(&mut self) -> Result<TickStatus> {
-        self.v_buffer.fill(false);
-        Ok(TickStatus::default())
+    fn exec_clear_screen(&mut self) -> TickStatus {
+        self.v_buffer.clear();
+        self.touch_screen();
+        TickStatus::default()
     }
 
     fn exec_return(&mut self) -> Result<TickStatus> {
@@ -287,9 +385,9 @@ impl<'a> CPU<'a> {
         Ok(TickStatus::default())
     }
 
-    fn exec_jump(&mut self, to: u16) -> Result<TickStatus> {
+    fn exec_jump(&mut self, to: u16) -> TickStatus {
         self.pc = to;
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
     fn exec_call(&mut self, to: u16) -> Result<TickStatus> {
@@ -298,244 +396,254 @@ impl<'a> CPU<'a> {
         Ok(TickStatus::default())
     }
 
-    fn exec_skip_vx_if_equal(&mut self, x: u8, value: u8) -> Result<TickStatus> {
-        if self.read_register(x)? == value {
+    fn exec_skip_vx_if_equal(&mut self, x: VReg, value: u8) -> TickStatus {
+        if self.read_register(x) == value {
             self.pc += 2;
         }
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_skip_vx_if_not_equal(&mut self, x: u8, value: u8) -> Result<TickStatus> {
-        if self.read_register(x)? != value {
+    fn exec_skip_vx_if_not_equal(&mut self, x: VReg, value: u8) -> TickStatus {
+        if self.read_register(x) != value {
             self.pc += 2;
         }
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_skip_if_equal(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        if self.read_register(x)? == self.read_register(y)? {
+    fn exec_skip_if_equal(&mut self, x: VReg, y: VReg) -> TickStatus {
+        if self.read_register(x) == self.read_register(y) {
             self.pc += 2;
         }
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_load_vx(&mut self, x: u8, value: u8) -> Result<TickStatus> {
-        self.set_register(x, value)?;
-        Ok(TickStatus::default())
+    fn exec_load_vx(&mut self, x: VReg, value: u8) -> TickStatus {
+        self.set_register(x, value);
+        TickStatus::default()
     }
 
-    fn exec_add_vx(&mut self, vx: u8, value: u8) -> Result<TickStatus> {
-        let i = self
-            .v_registers
-            .get_mut(vx as usize)
-            .ok_or(CPUError::InvalidVRegister(vx))?;
-
-        let (added, _) = i.overflowing_add(value);
-        *i = added;
+    fn exec_add_vx(&mut self, vx: VReg, value: u8) -> TickStatus {
+        let added = self.read_register(vx).wrapping_add(value);
+        self.set_register(vx, added);
 
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_set(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        let value = self.read_register(y)?;
-        self.set_register(x, value)?;
-        Ok(TickStatus::default())
+    fn exec_set(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let value = self.read_register(y);
+        self.set_register(x, value);
+        TickStatus::default()
     }
 
-    fn exec_or(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        let value = self.read_register(x)? | self.read_register(y)?;
-        self.set_register(x, value)?;
-        Ok(TickStatus::default())
+    fn exec_or(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let value = self.read_register(x) | self.read_register(y);
+        self.set_register(x, value);
+        self.reset_flag_after_logic();
+        TickStatus::default()
     }
 
-    fn exec_and(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        let value = self.read_register(x)? & self.read_register(y)?;
-        self.set_register(x, value)?;
-        Ok(TickStatus::default())
+    fn exec_and(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let value = self.read_register(x) & self.read_register(y);
+        self.set_register(x, value);
+        self.reset_flag_after_logic();
+        TickStatus::default()
     }
 
-    fn exec_xor(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        let value = self.read_register(x)? ^ self.read_register(y)?;
-        self.set_register(x, value)?;
-        Ok(TickStatus::default())
+    fn exec_xor(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let value = self.read_register(x) ^ self.read_register(y);
+        self.set_register(x, value);
+        self.reset_flag_after_logic();
+        TickStatus::default()
     }
 
-    fn exec_add(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        let (value, carry) = self
-            .read_register(x)?
-            .overflowing_add(self.read_register(y)?);
-        self.set_register(x, value)?;
-        self.set_register(0xF, carry as u8)?;
-        Ok(TickStatus::default())
+    fn exec_add(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let (value, carry) = self.read_register(x).overflowing_add(self.read_register(y));
+        self.set_register(x, value);
+        self.set_register(VReg::VF, carry as u8);
+        TickStatus::default()
     }
 
-    fn exec_sub(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        let (value, carry) = self
-            .read_register(x)?
-            .overflowing_sub(self.read_register(y)?);
-        self.set_register(x, value)?;
-        self.set_register(0xF, !carry as u8)?;
-        Ok(TickStatus::default())
+    fn exec_sub(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let (value, carry) = self.read_register(x).overflowing_sub(self.read_register(y));
+        self.set_register(x, value);
+        self.set_register(VReg::VF, !carry as u8);
+        TickStatus::default()
     }
 
-    fn exec_shiftr_vx(&mut self, x: u8) -> Result<TickStatus> {
-        let value = self.read_register(x)?;
+    fn exec_shiftr_vx(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let value = self.read_register(if self.quirks.shift { x } else { y });
         let shifted_out = value & 0b_0000_0001;
-        self.set_register(x, value >> 1)?;
-        self.set_register(0xF, shifted_out)?;
-        Ok(TickStatus::default())
+        self.set_register(x, value >> 1);
+        self.set_register(VReg::VF, shifted_out);
+        TickStatus::default()
     }
 
-    fn exec_subn(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        let (value, carry) = self
-            .read_register(y)?
-            .overflowing_sub(self.read_register(x)?);
-        self.set_register(x, value)?;
-        self.set_register(0xF, !carry as u8)?;
-        Ok(TickStatus::default())
+    fn exec_subn(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let (value, carry) = self.read_register(y).overflowing_sub(self.read_register(x));
+        self.set_register(x, value);
+        self.set_register(VReg::VF, !carry as u8);
+        TickStatus::default()
     }
 
-    fn exec_shiftl_vx(&mut self, x: u8) -> Result<TickStatus> {
-        let value = self.read_register(x)?;
+    fn exec_shiftl_vx(&mut self, x: VReg, y: VReg) -> TickStatus {
+        let value = self.read_register(if self.quirks.shift { x } else { y });
         let shifted_out = (value & 0b_1000_0000) >> 7;
-        self.set_register(x, value << 1)?;
-        self.set_register(0xF, shifted_out)?;
-        Ok(TickStatus::default())
+        self.set_register(x, value << 1);
+        self.set_register(VReg::VF, shifted_out);
+        TickStatus::default()
     }
 
-    fn exec_skip_if_not_equal(&mut self, x: u8, y: u8) -> Result<TickStatus> {
-        if self.read_register(x)? != self.read_register(y)? {
+    fn exec_skip_if_not_equal(&mut self, x: VReg, y: VReg) -> TickStatus {
+        if self.read_register(x) != self.read_register(y) {
             self.pc += 2;
         }
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_load_i(&mut self, value: u16) -> Result<TickStatus> {
+    fn exec_load_i(&mut self, value: u16) -> TickStatus {
         self.i_register = value;
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_jump_offset(&mut self, x: u8, addr: u16) -> Result<TickStatus> {
-        let offset = self.read_register(x)?;
+    fn exec_jump_offset(&mut self, x: VReg, addr: u16) -> TickStatus {
+        let offset = self.read_register(if self.quirks.jump { x } else { VReg::new(0x0) });
         self.pc = addr + offset as u16;
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_rand(&mut self, x: u8, value: u8) -> Result<TickStatus> {
+    fn exec_rand(&mut self, x: VReg, value: u8) -> TickStatus {
         let randomized: u8 = self.rng.gen();
-        self.set_register(x, randomized & value)?;
+        self.set_register(x, randomized & value);
 
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_draw_sprite(&mut self, vx: u8, vy: u8, n: u8) -> Result<TickStatus> {
+    fn exec_draw_sprite(&mut self, vx: VReg, vy: VReg, n: u8) -> Result<TickStatus> {
         let sprite = sprites::read_sprite(self.i_register as usize, n as usize, &self.memory)?;
 
-        let x = self.read_register(vx)?;
-        let y = self.read_register(vy)?;
+        let x = self.read_register(vx);
+        let y = self.read_register(vy);
 
-        let did_collide = sprites::draw(
-            sprite,
-            x as usize,
-            y as usize,
-            (SCREEN_WIDTH, SCREEN_HEIGHT),
-            &mut self.v_buffer,
-        );
+        let wrap = self.quirks.wrap;
+        let did_collide = self
+            .v_buffer
+            .draw_sprite(sprite, x as usize, y as usize, wrap);
+        self.touch_screen();
 
-        self.v_registers[0xF] = did_collide as u8;
+        self.set_register(VReg::VF, did_collide as u8);
+        self.is_waiting_for_vblank = self.quirks.vblank;
 
         Ok(TickStatus::default())
     }
 
-    fn exec_skip_if_key(&mut self, vx: u8) -> Result<TickStatus> {
-        let key_idx = self.read_register(vx)?;
-        let is_key_pressed = self.read_key(key_idx)?;
+    fn exec_skip_if_key(&mut self, vx: VReg) -> Result<TickStatus> {
+        let key = Key::try_from(self.read_register(vx))?;
 
-        if is_key_pressed {
+        if self.read_key(key) {
             self.pc += 2;
         }
 
         Ok(TickStatus::default())
     }
 
-    fn exec_skip_if_not_key(&mut self, vx: u8) -> Result<TickStatus> {
-        let key_idx = self.read_register(vx)?;
-        let is_key_pressed = self.read_key(key_idx)?;
+    fn exec_skip_if_not_key(&mut self, vx: VReg) -> Result<TickStatus> {
+        let key = Key::try_from(self.read_register(vx))?;
 
-        if !is_key_pressed {
+        if !self.read_key(key) {
             self.pc += 2;
         }
 
         Ok(TickStatus::default())
     }
 
-    fn exec_load_delay(&mut self, vx: u8) -> Result<TickStatus> {
-        self.set_register(vx, self.delay_timer)?;
-        Ok(TickStatus::default())
+    fn exec_load_delay(&mut self, vx: VReg) -> TickStatus {
+        self.set_register(vx, self.delay_timer);
+        TickStatus::default()
     }
 
-    fn exec_wait_for_key(&mut self, vx: u8) -> Result<TickStatus> {
-        let _ = self.read_register(vx)?; // ensure vx is valid
-        self.is_waiting_for_key = (true, vx as usize);
+    fn exec_wait_for_key(&mut self, vx: VReg) -> TickStatus {
+        self.is_waiting_for_key = (true, vx);
 
-        let mut status = TickStatus::default();
-        status.is_waiting_for_key = true;
-
-        Ok(status)
+        TickStatus {
+            is_waiting_for_key: true,
+            ..TickStatus::default()
+        }
     }
 
-    fn exec_set_delay(&mut self, vx: u8) -> Result<TickStatus> {
-        self.delay_timer = self.read_register(vx)?;
-        Ok(TickStatus::default())
+    fn exec_set_delay(&mut self, vx: VReg) -> TickStatus {
+        self.delay_timer = self.read_register(vx);
+        TickStatus::default()
     }
 
-    fn exec_set_sound(&mut self, vx: u8) -> Result<TickStatus> {
-        self.sound_timer = self.read_register(vx)?;
-        Ok(TickStatus::default())
+    fn exec_set_sound(&mut self, vx: VReg) -> TickStatus {
+        self.sound_timer = self.read_register(vx);
+        TickStatus::default()
     }
 
-    fn exec_add_to_index(&mut self, vx: u8) -> Result<TickStatus> {
-        let value = self.i_register + self.read_register(vx)? as u16;
+    fn exec_add_to_index(&mut self, vx: VReg) -> TickStatus {
+        let value = self.i_register + self.read_register(vx) as u16;
         let carry = self.set_i_register(value);
-        self.set_register(0xF, carry)?;
+        self.set_register(VReg::VF, carry);
 
-        Ok(TickStatus::default())
+        TickStatus::default()
     }
 
-    fn exec_load_digit(&mut self, vx: u8) -> Result<TickStatus> {
-        let digit = self.read_register(vx)?;
+    fn exec_load_digit(&mut self, vx: VReg) -> Result<TickStatus> {
+        let digit = self.read_register(vx);
         self.i_register = self.address_for_digit(digit)?;
 
         Ok(TickStatus::default())
     }
 
-    fn exec_load_bcd(&mut self, vx: u8) -> Result<TickStatus> {
-        let (hundreds, tens, ones) = self.read_register(vx)?.to_bcd();
+    fn exec_load_bcd(&mut self, vx: VReg) -> Result<TickStatus> {
+        let (hundreds, tens, ones) = self.read_register(vx).to_bcd();
         self.set_memory(self.i_register, hundreds)?;
         self.set_memory(self.i_register + 1, tens)?;
         self.set_memory(self.i_register + 2, ones)?;
         Ok(TickStatus::default())
     }
 
-    fn exec_load_mem(&mut self, vx: u8) -> Result<TickStatus> {
-        for i in 0..=vx {
-            let value = self.get_memory(self.i_register + i as u16)?;
-            self.set_register(i, value)?;
+    fn exec_load_mem(&mut self, vx: VReg) -> Result<TickStatus> {
+        for (offset, i) in vx.up_to().enumerate() {
+            let value = self.get_memory(self.i_register + offset as u16)?;
+            self.set_register(i, value);
         }
 
+        self.increment_i_after_memory(vx);
         Ok(TickStatus::default())
     }
 
-    fn exec_save_mem(&mut self, vx: u8) -> Result<TickStatus> {
-        for i in 0..=vx {
-            let value = self.read_register(i)?;
-            self.set_memory(self.i_register + i as u16, value)?;
+    fn exec_save_mem(&mut self, vx: VReg) -> Result<TickStatus> {
+        for (offset, i) in vx.up_to().enumerate() {
+            let value = self.read_register(i);
+            self.set_memory(self.i_register + offset as u16, value)?;
         }
 
+        self.increment_i_after_memory(vx);
         Ok(TickStatus::default())
     }
+
+    fn reset_flag_after_logic(&mut self) {
+        if self.quirks.logic {
+            self.set_register(VReg::VF, 0);
+        }
+    }
+
+    fn increment_i_after_memory(&mut self, vx: VReg) {
+        let increment = match (
+            self.quirks.memory_leave_i_unchanged,
+            self.quirks.memory_increment_by_x,
+        ) {
+            (true, _) => 0,
+            (false, true) => vx.index() as u16,
+            (false, false) => vx.index() as u16 + 1,
+        };
+
+        self.set_i_register(self.i_register + increment);
+    }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 trait BCD {
     fn to_bcd(&self) -> (u8, u8, u8);
 }
@@ -551,27 +659,56 @@ impl BCD for u8 {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::unusual_byte_groupings)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SCREEN_WIDTH: usize = 64;
+    const SCREEN_HEIGHT: usize = 32;
+
+    fn key(x: u8) -> Key {
+        Key::try_from(x).unwrap()
+    }
 
     fn any_mocked_rng() -> impl RngCore {
         rand::rngs::mock::StepRng::new(1, 1)
     }
 
-    fn any_cpu_with_rom<'a>(rom: &[u8], rng: &'a mut impl RngCore) -> CPU<'a> {
+    fn any_cpu_with_rom(rom: &[u8], rng: impl RngCore + Send + 'static) -> CPU {
         let mut cpu = CPU::new(rng);
         cpu.load_rom(rom).expect("Couldn't load ROM");
         cpu
     }
 
-    fn any_cpu_with_noop<'a>(rng: &'a mut impl RngCore) -> CPU<'a> {
+    fn any_cpu_with_noop(rng: impl RngCore + Send + 'static) -> CPU {
         any_cpu_with_rom(&[0x01, 0x23], rng)
     }
 
+    #[test]
+    fn test_snapshot() {
+        // V0 = 0x12, I = 0x300, CALL 0x208
+        let mut cpu = any_cpu_with_rom(&[0x60, 0x12, 0xA3, 0x00, 0x22, 0x08], any_mocked_rng());
+        cpu.set_key_status(key(0x5), true);
+        for _ in 0..3 {
+            cpu.tick().unwrap();
+        }
+
+        let snapshot = cpu.snapshot();
+
+        assert_eq!(snapshot.v_registers[0], 0x12);
+        assert_eq!(snapshot.i_register, 0x300);
+        assert_eq!(snapshot.pc, 0x208);
+        assert_eq!((snapshot.sp, snapshot.stack[0]), (1, 0x206));
+        assert!(snapshot.keypad[0x5]);
+        assert_eq!(snapshot.memory[0x200..0x202], [0x60, 0x12]);
+    }
+
     #[test]
     fn test_new() {
-        let mut rng = any_mocked_rng();
-        let cpu = CPU::new(&mut rng);
+        let rng = any_mocked_rng();
+        let cpu = CPU::new(rng);
         assert_eq!(cpu.memory[MEM_START..MEM_END], [0; MEM_END - MEM_START]);
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.v_registers, [0; 16]);
@@ -584,8 +721,8 @@ mod tests {
 
     #[test]
     fn test_load_rom_ok() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = CPU::new(&mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = CPU::new(rng);
         let rom: [u8; 2] = [0x00, 0xE0];
 
         let res = cpu.load_rom(&rom);
@@ -598,8 +735,8 @@ mod tests {
 
     #[test]
     fn test_load_rom_returns_error_on_memory_overflow() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = CPU::new(&mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = CPU::new(rng);
         let rom: [u8; 4096 - 199] = [0; 4096 - 199];
 
         let res = cpu.load_rom(&rom);
@@ -609,32 +746,20 @@ mod tests {
 
     #[test]
     fn test_set_key_status() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = CPU::new(&mut rng);
-
-        let res_down = cpu.set_key_status(0xF, true);
+        let rng = any_mocked_rng();
+        let mut cpu = CPU::new(rng);
 
-        assert!(res_down.is_ok());
+        cpu.set_key_status(key(0xF), true);
         assert_eq!(cpu.keypad[0xF], true);
 
-        let res_up = cpu.set_key_status(0xF, false);
-        assert!(res_up.is_ok());
+        cpu.set_key_status(key(0xF), false);
         assert_eq!(cpu.keypad[0xF], false);
     }
 
-    #[test]
-    fn test_set_key_status_returns_err() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = CPU::new(&mut rng);
-
-        let res = cpu.set_key_status(0x10, true);
-        assert_eq!(res.unwrap_err(), CPUError::InvalidKey(0x10));
-    }
-
     #[test]
     fn test_tick_returns_err_on_invalid_opcode() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xFF, 0xFF], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xFF, 0xFF], rng);
         let res = cpu.tick();
 
         assert_eq!(res.unwrap_err(), CPUError::InvalidOpcode(0xFFFF));
@@ -642,8 +767,8 @@ mod tests {
 
     #[test]
     fn test_tick_returns_err_if_invalid_pc() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[], rng);
         cpu.pc = 0x1000;
 
         let res = cpu.tick();
@@ -653,9 +778,9 @@ mod tests {
 
     #[test]
     fn test_tick_does_not_advance_if_waiting_for_key() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[], &mut rng);
-        cpu.is_waiting_for_key = (true, 0xF);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[], rng);
+        cpu.is_waiting_for_key = (true, VReg::new(0xF));
 
         let res = cpu.tick();
 
@@ -669,28 +794,56 @@ mod tests {
     }
 
     #[test]
-    fn test_tick_updates_timers() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[], &mut rng);
-        cpu.is_waiting_for_key = (true, 0x0);
+    fn test_tick_timers_updates_timers() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[], rng);
+        cpu.is_waiting_for_key = (true, VReg::new(0x0));
         cpu.delay_timer = 1;
         cpu.sound_timer = 1;
 
-        let res_1st_tick = cpu.tick();
-        assert!(res_1st_tick.is_ok());
+        cpu.tick_timers();
         assert_eq!(cpu.delay_timer, 0);
         assert_eq!(cpu.sound_timer, 0);
 
-        let res_2nd_tick = cpu.tick();
-        assert!(res_2nd_tick.is_ok());
+        cpu.tick_timers();
         assert_eq!(cpu.delay_timer, 0); // no overflow
         assert_eq!(cpu.sound_timer, 0); // no overflow
     }
 
+    #[test]
+    fn test_tick_does_not_update_timers() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_noop(rng);
+        cpu.delay_timer = 1;
+        cpu.sound_timer = 1;
+
+        let res = cpu.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(cpu.delay_timer, 1);
+        assert_eq!(cpu.sound_timer, 1);
+    }
+
+    #[test]
+    fn test_tick_does_not_advance_if_waiting_for_vblank() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_noop(rng);
+        cpu.is_waiting_for_vblank = true;
+
+        let res = cpu.tick();
+        assert!(res.is_ok());
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.tick_timers();
+        let res = cpu.tick();
+        assert!(res.is_ok());
+        assert_eq!(cpu.pc, 0x202);
+    }
+
     #[test]
     fn test_tick_returns_not_buzzing_when_sound_timer_is_zero() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_noop(&mut rng); // no op
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_noop(rng); // no op
         cpu.sound_timer = 0;
 
         let res = cpu.tick();
@@ -700,8 +853,8 @@ mod tests {
 
     #[test]
     fn test_tick_returns_is_buzzing_when_sound_timer_is_greater_than_zero() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_noop(&mut rng); // no op
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_noop(rng); // no op
         cpu.sound_timer = 0x20;
 
         let res = cpu.tick();
@@ -711,10 +864,10 @@ mod tests {
 
     #[test]
     fn test_tick_returns_is_buzzing_despite_waiting_for_key() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[], rng);
         cpu.sound_timer = 0x20;
-        cpu.is_waiting_for_key = (true, 0x0);
+        cpu.is_waiting_for_key = (true, VReg::new(0x0));
 
         let res = cpu.tick();
 
@@ -723,8 +876,8 @@ mod tests {
 
     #[test]
     fn test_noop() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x01, 0x23], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x01, 0x23], rng);
 
         let res = cpu.tick();
 
@@ -734,21 +887,35 @@ mod tests {
 
     #[test]
     fn test_clear_screen() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x00, 0xe0], &mut rng);
-        cpu.v_buffer = [true; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x00, 0xe0], rng);
+        cpu.v_buffer = Framebuffer::from([u64::MAX; SCREEN_HEIGHT]);
 
         let res = cpu.tick();
 
         assert!(res.is_ok());
         assert_eq!(cpu.pc, 0x0202);
-        assert_eq!(cpu.v_buffer, [false; SCREEN_WIDTH * SCREEN_HEIGHT]);
+        assert_eq!(cpu.v_buffer, Framebuffer::default());
+        assert_eq!(cpu.screen_generation(), 1);
+    }
+
+    #[test]
+    fn test_screen_generation_only_changes_when_drawing() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x60, 0x01, 0xD0, 0x01, 0x70, 0x01], rng);
+
+        cpu.tick().unwrap();
+        assert_eq!(cpu.screen_generation(), 0);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.screen_generation(), 1);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.screen_generation(), 1);
     }
 
     #[test]
     fn test_return() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x00, 0xee], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x00, 0xee], rng);
         cpu.stack[0] = 0x300;
         cpu.sp = 1;
 
@@ -761,8 +928,8 @@ mod tests {
 
     #[test]
     fn test_jump() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x13, 0x21], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x13, 0x21], rng);
 
         let res = cpu.tick();
 
@@ -772,8 +939,8 @@ mod tests {
 
     #[test]
     fn test_call() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x23, 0x21], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x23, 0x21], rng);
 
         let res = cpu.tick();
 
@@ -785,8 +952,8 @@ mod tests {
 
     #[test]
     fn test_call_stack_overflow() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x23, 0x21], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x23, 0x21], rng);
         cpu.sp = 16;
 
         let res = cpu.tick();
@@ -796,8 +963,8 @@ mod tests {
 
     #[test]
     fn test_skip_vx_if_equal_skips() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x30, 0x42], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x30, 0x42], rng);
         cpu.v_registers[0] = 0x42;
 
         let res = cpu.tick();
@@ -808,8 +975,8 @@ mod tests {
 
     #[test]
     fn test_skip_vx_if_equal_does_not_skip() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x30, 0x42], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x30, 0x42], rng);
         cpu.v_registers[0] = 0x00;
 
         let res = cpu.tick();
@@ -820,8 +987,8 @@ mod tests {
 
     #[test]
     fn test_skip_vx_if_not_equal_skips() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x40, 0x42], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x40, 0x42], rng);
         cpu.v_registers[0] = 0x00;
 
         let res = cpu.tick();
@@ -832,8 +999,8 @@ mod tests {
 
     #[test]
     fn test_skip_vx_if_not_equal_does_not_skip() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x40, 0x42], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x40, 0x42], rng);
         cpu.v_registers[0] = 0x42;
 
         let res = cpu.tick();
@@ -844,8 +1011,8 @@ mod tests {
 
     #[test]
     fn test_skip_if_equal_skips() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x50, 0x10], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x50, 0x10], rng);
         cpu.v_registers[0] = 0xFF;
         cpu.v_registers[1] = 0xFF;
 
@@ -857,8 +1024,8 @@ mod tests {
 
     #[test]
     fn test_skip_if_equal_does_not_skip() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x50, 0x10], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x50, 0x10], rng);
         cpu.v_registers[0] = 0x00;
         cpu.v_registers[1] = 0xFF;
 
@@ -870,8 +1037,8 @@ mod tests {
 
     #[test]
     fn test_load_vx() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x6A, 0x8F], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x6A, 0x8F], rng);
 
         let res = cpu.tick();
 
@@ -882,8 +1049,8 @@ mod tests {
 
     #[test]
     fn test_add_vx() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x7A, 0x8F], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x7A, 0x8F], rng);
         cpu.v_registers[0xA] = 0x1;
 
         let res = cpu.tick();
@@ -895,8 +1062,8 @@ mod tests {
 
     #[test]
     fn test_set() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x10], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x10], rng);
         cpu.v_registers[0x1] = 0xA;
 
         let res = cpu.tick();
@@ -909,8 +1076,8 @@ mod tests {
 
     #[test]
     fn test_or() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x11], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x11], rng);
         cpu.v_registers[0x0] = 0b_0001_1111;
         cpu.v_registers[0x1] = 0b_0110_1111;
 
@@ -924,8 +1091,8 @@ mod tests {
 
     #[test]
     fn test_and() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x12], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x12], rng);
         cpu.v_registers[0x0] = 0b_0001_1111;
         cpu.v_registers[0x1] = 0b_0110_1111;
 
@@ -939,8 +1106,8 @@ mod tests {
 
     #[test]
     fn test_xor() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x13], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x13], rng);
         cpu.v_registers[0x0] = 0b_0001_1111;
         cpu.v_registers[0x1] = 0b_0110_1111;
 
@@ -952,10 +1119,28 @@ mod tests {
         assert_eq!(cpu.v_registers[0x1], 0b_0110_1111);
     }
 
+    #[test]
+    fn test_logic_resets_vf_with_quirk() {
+        for opcode in [0x11, 0x12, 0x13] {
+            let rng = any_mocked_rng();
+            let mut cpu = any_cpu_with_rom(&[0x80, opcode], rng);
+            cpu.set_quirks(Quirks {
+                logic: true,
+                ..Quirks::default()
+            });
+            cpu.v_registers[0xF] = 0x01;
+
+            let res = cpu.tick();
+
+            assert!(res.is_ok());
+            assert_eq!(cpu.v_registers[0xF], 0x00);
+        }
+    }
+
     #[test]
     fn test_add() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x14], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x14], rng);
         cpu.v_registers[0x0] = 0x0F;
         cpu.v_registers[0x1] = 0x11;
 
@@ -970,8 +1155,8 @@ mod tests {
 
     #[test]
     fn test_add_overflow() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x14], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x14], rng);
         cpu.v_registers[0x0] = 0xFD;
         cpu.v_registers[0x1] = 0x04;
 
@@ -986,8 +1171,8 @@ mod tests {
 
     #[test]
     fn test_sub() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x15], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x15], rng);
         cpu.v_registers[0x0] = 0xF0;
         cpu.v_registers[0x1] = 0x11;
 
@@ -1002,8 +1187,8 @@ mod tests {
 
     #[test]
     fn test_sub_overflow() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x15], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x15], rng);
         cpu.v_registers[0x0] = 0xF0;
         cpu.v_registers[0x1] = 0xF1;
 
@@ -1018,8 +1203,8 @@ mod tests {
 
     #[test]
     fn test_shift_right_vx() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x16], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x16], rng);
         cpu.v_registers[0x0] = 0b_0100_1110;
 
         let res = cpu.tick();
@@ -1032,8 +1217,8 @@ mod tests {
 
     #[test]
     fn test_shift_right_vx_with_shifted_out_bit() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x16], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x16], rng);
         cpu.v_registers[0x0] = 0b_0100_1111;
 
         let res = cpu.tick();
@@ -1044,10 +1229,29 @@ mod tests {
         assert_eq!(cpu.v_registers[0xF], 0x01);
     }
 
+    #[test]
+    fn test_shift_right_vy_without_quirk() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x16], rng);
+        cpu.set_quirks(Quirks {
+            shift: false,
+            ..Quirks::default()
+        });
+        cpu.v_registers[0x0] = 0b_0100_1110;
+        cpu.v_registers[0x1] = 0b_0000_0011;
+
+        let res = cpu.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(cpu.v_registers[0x0], 0b_0000_0001);
+        assert_eq!(cpu.v_registers[0x1], 0b_0000_0011);
+        assert_eq!(cpu.v_registers[0xF], 0x01);
+    }
+
     #[test]
     fn test_subn() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x17], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x17], rng);
         cpu.v_registers[0x0] = 0x11;
         cpu.v_registers[0x1] = 0xF0;
 
@@ -1062,8 +1266,8 @@ mod tests {
 
     #[test]
     fn test_subn_overflow() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x17], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x17], rng);
         cpu.v_registers[0x0] = 0xF1;
         cpu.v_registers[0x1] = 0xF0;
 
@@ -1078,8 +1282,8 @@ mod tests {
 
     #[test]
     fn test_shift_left_vx() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x1E], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x1E], rng);
         cpu.v_registers[0x0] = 0b_0100_1110;
 
         let res = cpu.tick();
@@ -1092,8 +1296,8 @@ mod tests {
 
     #[test]
     fn test_shift_left_vx_with_shifted_out_bit() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x80, 0x1E], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x1E], rng);
         cpu.v_registers[0x0] = 0b_1100_1111;
 
         let res = cpu.tick();
@@ -1104,10 +1308,28 @@ mod tests {
         assert_eq!(cpu.v_registers[0xF], 0x01);
     }
 
+    #[test]
+    fn test_shift_left_vy_without_quirk() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x80, 0x1E], rng);
+        cpu.set_quirks(Quirks {
+            shift: false,
+            ..Quirks::default()
+        });
+        cpu.v_registers[0x0] = 0b_0100_1110;
+        cpu.v_registers[0x1] = 0b_1000_0001;
+
+        let res = cpu.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(cpu.v_registers[0x0], 0b_0000_0010);
+        assert_eq!(cpu.v_registers[0xF], 0x01);
+    }
+
     #[test]
     fn test_skip_if_not_equal_skips() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x90, 0x10], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x90, 0x10], rng);
         cpu.v_registers[0] = 0xFF;
         cpu.v_registers[1] = 0x0F;
 
@@ -1119,8 +1341,8 @@ mod tests {
 
     #[test]
     fn test_skip_if_not_equal_does_not_skip() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0x90, 0x10], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x90, 0x10], rng);
         cpu.v_registers[0] = 0xFF;
         cpu.v_registers[1] = 0xFF;
 
@@ -1132,8 +1354,8 @@ mod tests {
 
     #[test]
     fn test_load_i() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xA1, 0x23], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xA1, 0x23], rng);
 
         let res = cpu.tick();
 
@@ -1144,8 +1366,8 @@ mod tests {
 
     #[test]
     fn test_jump_offset() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xB2, 0x23], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xB2, 0x23], rng);
         cpu.v_registers[0x2] = 0x10;
 
         let res = cpu.tick();
@@ -1154,10 +1376,27 @@ mod tests {
         assert_eq!(cpu.pc, 0x233);
     }
 
+    #[test]
+    fn test_jump_offset_with_v0_without_quirk() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xB2, 0x23], rng);
+        cpu.set_quirks(Quirks {
+            jump: false,
+            ..Quirks::default()
+        });
+        cpu.v_registers[0x0] = 0x01;
+        cpu.v_registers[0x2] = 0x10;
+
+        let res = cpu.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(cpu.pc, 0x224);
+    }
+
     #[test]
     fn test_rand() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xC0, 0xAB], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xC0, 0xAB], rng);
 
         let res = cpu.tick();
 
@@ -1165,10 +1404,34 @@ mod tests {
         assert_eq!(cpu.v_registers[0], 0x01 & 0xAB);
     }
 
+    #[test]
+    fn test_rand_is_deterministic_for_a_seed() {
+        let values = |seed| {
+            let rng = StdRng::seed_from_u64(seed);
+            let mut cpu = any_cpu_with_rom(&[0xC0, 0xFF, 0x12, 0x00], rng);
+            (0..8)
+                .map(|_| {
+                    cpu.tick().unwrap();
+                    cpu.tick().unwrap();
+                    cpu.v_registers[0]
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        assert_eq!(values(42), values(42));
+        assert_ne!(values(42), values(43));
+    }
+
+    #[test]
+    fn test_cpu_is_send_and_static() {
+        fn assert_send<T: Send + 'static>() {}
+        assert_send::<CPU>();
+    }
+
     #[test]
     fn test_draw_sprite_simple() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xD0, 0x13], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xD0, 0x13], rng);
         cpu.i_register = 0x300;
         cpu.v_registers[0] = 0x1;
         cpu.v_registers[1] = 0x2;
@@ -1177,20 +1440,21 @@ mod tests {
         cpu.memory[0x302] = 0xFF;
 
         let res = cpu.tick();
+        let pixels: Vec<bool> = cpu.v_buffer.pixels().collect();
 
         let i = (2 * SCREEN_WIDTH) + 1;
         assert!(res.is_ok());
         assert_eq!(cpu.pc, 0x0202);
-        assert_eq!(cpu.v_buffer[i..(i + 8)], [true; 8]);
-        assert_eq!(cpu.v_buffer[i + 64..(i + 64 + 8)], [false; 8]);
-        assert_eq!(cpu.v_buffer[i + 128..(i + 128 + 8)], [true; 8]);
+        assert_eq!(pixels[i..(i + 8)], [true; 8]);
+        assert_eq!(pixels[i + 64..(i + 64 + 8)], [false; 8]);
+        assert_eq!(pixels[i + 128..(i + 128 + 8)], [true; 8]);
         assert_eq!(cpu.v_registers[0xF], 0);
     }
 
     #[test]
     fn test_draw_sprite_wraps() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xD0, 0x13], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xD0, 0x13], rng);
         cpu.i_register = 0x300;
         cpu.v_registers[0] = 60;
         cpu.v_registers[1] = 30;
@@ -1199,37 +1463,84 @@ mod tests {
         cpu.memory[0x302] = 0xFF;
 
         let res = cpu.tick();
+        let pixels: Vec<bool> = cpu.v_buffer.pixels().collect();
 
         let mut i = (30 * SCREEN_WIDTH) + 60;
         assert!(res.is_ok());
         assert_eq!(cpu.pc, 0x0202);
-        assert_eq!(cpu.v_buffer[i..(i + 4)], [true; 4]);
-        assert_eq!(cpu.v_buffer[i - 60..(i - 60 + 4)], [true; 4]);
+        assert_eq!(pixels[i..(i + 4)], [true; 4]);
+        assert_eq!(pixels[i - 60..(i - 60 + 4)], [true; 4]);
         i = (30 * SCREEN_WIDTH) + 64;
-        assert_eq!(cpu.v_buffer[i..(i + 4)], [false; 4]);
-        assert_eq!(cpu.v_buffer[i - 60..(i - 60 + 4)], [false; 4]);
+        assert_eq!(pixels[i..(i + 4)], [false; 4]);
+        assert_eq!(pixels[i - 60..(i - 60 + 4)], [false; 4]);
         i = 60;
-        assert_eq!(cpu.v_buffer[i..(i + 4)], [true; 4]);
-        assert_eq!(cpu.v_buffer[i - 60..(i - 60 + 4)], [true; 4]);
+        assert_eq!(pixels[i..(i + 4)], [true; 4]);
+        assert_eq!(pixels[i - 60..(i - 60 + 4)], [true; 4]);
         assert_eq!(cpu.v_registers[0xF], 0);
     }
 
+    #[test]
+    fn test_draw_sprite_clips_without_wrap_quirk() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xD0, 0x13], rng);
+        cpu.set_quirks(Quirks {
+            wrap: false,
+            ..Quirks::default()
+        });
+        cpu.i_register = 0x300;
+        cpu.v_registers[0] = 60;
+        cpu.v_registers[1] = 30;
+        cpu.memory[0x300] = 0xFF;
+        cpu.memory[0x301] = 0x00;
+        cpu.memory[0x302] = 0xFF;
+
+        let res = cpu.tick();
+        let pixels: Vec<bool> = cpu.v_buffer.pixels().collect();
+
+        let i = (30 * SCREEN_WIDTH) + 60;
+        assert!(res.is_ok());
+        assert_eq!(pixels[i..(i + 4)], [true; 4]);
+        assert_eq!(pixels[i - 60..(i - 60 + 4)], [false; 4]);
+        assert_eq!(pixels[0..SCREEN_WIDTH], [false; SCREEN_WIDTH]);
+    }
+
+    #[test]
+    fn test_draw_sprite_waits_for_vblank_with_quirk() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xD0, 0x11, 0x01, 0x23], rng);
+        cpu.set_quirks(Quirks {
+            vblank: true,
+            ..Quirks::default()
+        });
+
+        assert!(cpu.tick().is_ok());
+        assert!(cpu.tick().is_ok());
+        assert_eq!(cpu.pc, 0x202);
+
+        cpu.tick_timers();
+        assert!(cpu.tick().is_ok());
+        assert_eq!(cpu.pc, 0x204);
+    }
+
     #[test]
     fn test_draw_sprite_with_collision() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xD0, 0x11], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xD0, 0x11], rng);
         cpu.i_register = 0x300;
         cpu.v_registers[0] = 0;
         cpu.v_registers[1] = 0;
         cpu.memory[0x300] = 0xFF;
-        cpu.v_buffer[0..8].copy_from_slice(&[false, false, false, false, true, true, true, true]);
+        let mut rows = [0; SCREEN_HEIGHT];
+        rows[0] = 0x0F << 56;
+        cpu.v_buffer = Framebuffer::from(rows);
 
         let res = cpu.tick();
+        let pixels: Vec<bool> = cpu.v_buffer.pixels().collect();
 
         assert!(res.is_ok());
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(
-            cpu.v_buffer[0..8],
+            pixels[0..8],
             [true, true, true, true, false, false, false, false]
         );
         assert_eq!(cpu.v_registers[0xF], 1);
@@ -1237,8 +1548,8 @@ mod tests {
 
     #[test]
     fn test_skip_if_key_skips_when_key_is_pressed() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xE0, 0x9E], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xE0, 0x9E], rng);
         cpu.v_registers[0x0] = 0x07;
         cpu.keypad[0x07] = true;
 
@@ -1250,8 +1561,8 @@ mod tests {
 
     #[test]
     fn test_skip_if_key_does_not_skip_when_key_is_not_pressed() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xE0, 0x9E], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xE0, 0x9E], rng);
         cpu.v_registers[0x0] = 0x07;
         cpu.keypad[0x07] = false;
 
@@ -1263,8 +1574,8 @@ mod tests {
 
     #[test]
     fn test_skip_if_key_returns_error_when_invalid_key() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xE0, 0x9E], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xE0, 0x9E], rng);
         cpu.v_registers[0x0] = 0x10;
 
         let res = cpu.tick();
@@ -1274,8 +1585,8 @@ mod tests {
 
     #[test]
     fn test_skip_if_not_key_skips_when_key_is_not_pressed() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xE0, 0xA1], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xE0, 0xA1], rng);
         cpu.v_registers[0x0] = 0x07;
         cpu.keypad[0x07] = false;
 
@@ -1287,8 +1598,8 @@ mod tests {
 
     #[test]
     fn test_skip_if_not_key_does_not_skip_when_key_is_pressed() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xE0, 0xA1], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xE0, 0xA1], rng);
         cpu.v_registers[0x0] = 0x07;
         cpu.keypad[0x07] = true;
 
@@ -1300,9 +1611,9 @@ mod tests {
 
     #[test]
     fn test_load_delay() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x07], &mut rng);
-        cpu.delay_timer = 0xCC + 0x01; // +1 because it will be decremented with tick
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x07], rng);
+        cpu.delay_timer = 0xCC;
 
         let res = cpu.tick();
 
@@ -1313,8 +1624,8 @@ mod tests {
 
     #[test]
     fn test_wait_for_key() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF1, 0x0A], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF1, 0x0A], rng);
 
         let res = cpu.tick();
         assert_eq!(
@@ -1325,19 +1636,41 @@ mod tests {
             }
         );
         assert_eq!(cpu.pc, 0x202);
-        assert_eq!(cpu.is_waiting_for_key, (true, 0x01));
+        assert_eq!(cpu.is_waiting_for_key, (true, VReg::new(0x01)));
 
         // unblocked execution with a key pressed
-        cpu.set_key_status(0xF, true).unwrap();
+        cpu.set_key_status(key(0xF), true);
+
+        assert_eq!(cpu.is_waiting_for_key, (false, VReg::new(0x00)));
+        assert_eq!(cpu.v_registers[0x01], 0x0F);
+    }
+
+    #[test]
+    fn test_wait_for_key_on_release() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF1, 0x0A], rng);
+        cpu.set_key_wait(KeyWait::Release);
+        cpu.set_key_status(key(0xA), true); // held before waiting
+
+        let res = cpu.tick();
+        assert!(res.unwrap().is_waiting_for_key);
+
+        // releasing a key pressed before waiting does not unblock execution
+        cpu.set_key_status(key(0xA), false);
+        assert_eq!(cpu.is_waiting_for_key, (true, VReg::new(0x01)));
+
+        cpu.set_key_status(key(0xF), true);
+        assert_eq!(cpu.is_waiting_for_key, (true, VReg::new(0x01)));
 
-        assert_eq!(cpu.is_waiting_for_key, (false, 0x00));
+        cpu.set_key_status(key(0xF), false);
+        assert_eq!(cpu.is_waiting_for_key, (false, VReg::new(0x00)));
         assert_eq!(cpu.v_registers[0x01], 0x0F);
     }
 
     #[test]
     fn test_set_delay() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x15], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x15], rng);
         cpu.v_registers[0x0] = 0xFA;
 
         let res = cpu.tick();
@@ -1349,8 +1682,8 @@ mod tests {
 
     #[test]
     fn test_set_sound() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x18], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x18], rng);
         cpu.v_registers[0x0] = 0xFA;
 
         let res = cpu.tick();
@@ -1362,8 +1695,8 @@ mod tests {
 
     #[test]
     fn test_add_to_index() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x1E], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x1E], rng);
         cpu.i_register = 0xFA;
         cpu.v_registers[0] = 0x02;
 
@@ -1376,8 +1709,8 @@ mod tests {
 
     #[test]
     fn test_add_to_index_overflows() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x1E], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x1E], rng);
         cpu.i_register = 0xFFE;
         cpu.v_registers[0x0] = 0x02;
         cpu.v_registers[0xF] = 0x0;
@@ -1392,8 +1725,8 @@ mod tests {
 
     #[test]
     fn test_load_digit() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x29], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x29], rng);
         cpu.v_registers[0x0] = 0xA;
         cpu.i_register = 0;
 
@@ -1409,8 +1742,8 @@ mod tests {
 
     #[test]
     fn test_load_digit_returns_invalid_digit_error() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x29], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x29], rng);
         cpu.v_registers[0x0] = 0x10;
 
         let res = cpu.tick();
@@ -1420,8 +1753,8 @@ mod tests {
 
     #[test]
     fn test_load_bcd() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x33], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x33], rng);
         cpu.v_registers[0x0] = 251;
         cpu.i_register = 0x500;
 
@@ -1436,8 +1769,8 @@ mod tests {
 
     #[test]
     fn test_load_bcd_returns_invalid_address_error() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF0, 0x33], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF0, 0x33], rng);
         cpu.i_register = 0xFFF;
 
         let res = cpu.tick();
@@ -1446,8 +1779,8 @@ mod tests {
 
     #[test]
     fn test_load_mem() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF2, 0x65], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF2, 0x65], rng);
         cpu.i_register = 0x500;
         cpu.memory[0x500..=0x503].copy_from_slice(&[0x02, 0x04, 0x06, 0xFF]);
 
@@ -1463,8 +1796,8 @@ mod tests {
 
     #[test]
     fn test_load_mem_returns_invalid_address_error() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF1, 0x65], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF1, 0x65], rng);
         cpu.i_register = 0xFFF;
 
         let res = cpu.tick();
@@ -1473,8 +1806,8 @@ mod tests {
 
     #[test]
     fn test_save_mem() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF2, 0x55], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF2, 0x55], rng);
         cpu.i_register = 0x500;
         cpu.v_registers[0x0] = 0x02;
         cpu.v_registers[0x1] = 0x04;
@@ -1489,10 +1822,80 @@ mod tests {
         assert_eq!(cpu.memory[0x500..=0x502], [0x02u8, 0x04u8, 0x06u8]);
     }
 
+    #[test]
+    fn test_save_mem_invalidates_decoded_instructions() {
+        let rng = any_mocked_rng();
+        #[rustfmt::skip]
+        let mut cpu = any_cpu_with_rom(&[
+            0x22, 0x0C, // call 0x20C
+            0xA2, 0x0C, // I = 0x20C
+            0x60, 0x62, // V0 = 0x62
+            0x61, 0x09, // V1 = 0x09
+            0xF1, 0x55, // [I] = V0..V1, which replaces 0x20C with V2 = 9
+            0x22, 0x0C, // call 0x20C
+            0x62, 0x05, // V2 = 5
+            0x00, 0xEE, // return
+        ], rng);
+
+        for _ in 0..3 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.v_registers[0x2], 5);
+
+        for _ in 0..6 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.v_registers[0x2], 9);
+    }
+
+    #[test]
+    fn test_load_rom_invalidates_decoded_instructions() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0x60, 0x01], rng);
+        cpu.tick().unwrap();
+
+        cpu.load_rom(&[0x60, 0x02]).unwrap();
+        cpu.pc = 0x200;
+        cpu.tick().unwrap();
+
+        assert_eq!(cpu.v_registers[0x0], 2);
+    }
+
+    #[test]
+    fn test_save_mem_increments_i_without_quirk() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF2, 0x55], rng);
+        cpu.set_quirks(Quirks {
+            memory_leave_i_unchanged: false,
+            ..Quirks::default()
+        });
+        cpu.i_register = 0x500;
+
+        let res = cpu.tick();
+        assert!(res.is_ok());
+        assert_eq!(cpu.i_register, 0x503);
+    }
+
+    #[test]
+    fn test_load_mem_increments_i_by_x_with_quirk() {
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF2, 0x65], rng);
+        cpu.set_quirks(Quirks {
+            memory_leave_i_unchanged: false,
+            memory_increment_by_x: true,
+            ..Quirks::default()
+        });
+        cpu.i_register = 0x500;
+
+        let res = cpu.tick();
+        assert!(res.is_ok());
+        assert_eq!(cpu.i_register, 0x502);
+    }
+
     #[test]
     fn test_save_mem_returns_invalid_address_error() {
-        let mut rng = any_mocked_rng();
-        let mut cpu = any_cpu_with_rom(&[0xF1, 0x55], &mut rng);
+        let rng = any_mocked_rng();
+        let mut cpu = any_cpu_with_rom(&[0xF1, 0x55], rng);
         cpu.i_register = 0xFFF;
 
         let res = cpu.tick();
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::quirks::{QuirkOverrides, Quirks};
+
+// Vendored from the CHIP-8 database: https://github.com/chip-8/chip-8-database
+const PROGRAMS: &str = include_str!("../database/programs.json");
+const PLATFORMS: &str = include_str!("../database/platforms.json");
+const HASHES: &str = include_str!("../database/sha1-hashes.json");
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    release: Option<String>,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    file: String,
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkOverrides>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    name: String,
+    default_tickrate: u32,
+    quirks: QuirkOverrides,
+}
+
+// The database files, parsed on the first lookup
+struct Database {
+    hashes: HashMap<String, usize>,
+    programs: Vec<Program>,
+    platforms: Vec<Platform>,
+}
+
+fn database() -> &'static Database {
+    static DATABASE: OnceLock<Database> = OnceLock::new();
+    DATABASE.get_or_init(|| Database {
+        hashes: serde_json::from_str(HASHES).expect("Invalid CHIP-8 database hashes"),
+        programs: serde_json::from_str(PROGRAMS).expect("Invalid CHIP-8 database programs"),
+        platforms: serde_json::from_str(PLATFORMS).expect("Invalid CHIP-8 database platforms"),
+    })
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct Colors {
+    // background first, then foreground, as "#rrggbb"
+    pub pixels: Vec<String>,
+}
+
+/// The settings of a ROM found in the CHIP-8 database
+#[derive(Debug, PartialEq, Clone)]
+pub struct RomInfo {
+    pub hash: String,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    pub file: String,
+    pub platform: Option<String>,
+    // instructions per frame
+    pub tickrate: Option<u32>,
+    pub quirks: Quirks,
+    // game actions ("up", "a", ...) to CHIP-8 keys
+    pub keys: HashMap<String, u8>,
+    pub colors: Option<Colors>,
+}
+
+pub fn sha1(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+pub fn lookup(rom: &[u8]) -> Option<RomInfo> {
+    lookup_hash(&sha1(rom))
+}
+
+pub fn lookup_hash(hash: &str) -> Option<RomInfo> {
+    let database = database();
+    let program = database.programs.get(*database.hashes.get(hash)?)?;
+    let rom = program.roms.get(hash)?;
+
+    // the first platform is the one the ROM is meant for, and ROMs that need
+    // quirks no platform has only list "quirky" ones, of which the first in
+    // the platforms file is picked
+    let platform = match rom.platforms.first() {
+        Some(id) => database
+            .platforms
+            .iter()
+            .find(|platform| platform.id == *id),
+        None => database
+            .platforms
+            .iter()
+            .find(|platform| rom.quirky_platforms.contains_key(&platform.id)),
+    };
+
+    let quirks = match platform {
+        Some(platform) => {
+            // quirks missing from a platform are disabled
+            let quirks = Quirks::default()
+                .with_overrides(&QUIRKS_DISABLED)
+                .with_overrides(&platform.quirks);
+            match rom.quirky_platforms.get(&platform.id) {
+                Some(overrides) => quirks.with_overrides(overrides),
+                None => quirks,
+            }
+        }
+        None => Quirks::default(),
+    };
+
+    Some(RomInfo {
+        hash: hash.to_string(),
+        title: program.title.clone(),
+        authors: program.authors.clone(),
+        release: program.release.clone(),
+        file: rom.file.clone(),
+        platform: platform.map(|platform| platform.name.clone()),
+        tickrate: rom
+            .tickrate
+            .or(platform.map(|platform| platform.default_tickrate)),
+        quirks,
+        keys: rom.keys.clone(),
+        colors: rom.colors.clone(),
+    })
+}
+
+const QUIRKS_DISABLED: QuirkOverrides = QuirkOverrides {
+    shift: Some(false),
+    memory_increment_by_x: Some(false),
+    memory_leave_i_unchanged: Some(false),
+    wrap: Some(false),
+    jump: Some(false),
+    vblank: Some(false),
+    logic: Some(false),
+};
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Title:    {}", self.title)?;
+        if !self.authors.is_empty() {
+            writeln!(f, "Authors:  {}", self.authors.join(", "))?;
+        }
+        if let Some(release) = &self.release {
+            writeln!(f, "Release:  {}", release)?;
+        }
+        writeln!(f, "File:     {}", self.file)?;
+        writeln!(f, "SHA-1:    {}", self.hash)?;
+        if let Some(platform) = &self.platform {
+            writeln!(f, "Platform: {}", platform)?;
+        }
+        if let Some(tickrate) = self.tickrate {
+            writeln!(f, "Tickrate: {} instructions per frame", tickrate)?;
+        }
+
+        let q = &self.quirks;
+        writeln!(f, "Quirks:")?;
+        writeln!(f, "  shift:                    {}", q.shift)?;
+        writeln!(f, "  memory increment by x:    {}", q.memory_increment_by_x)?;
+        writeln!(
+            f,
+            "  memory leave I unchanged: {}",
+            q.memory_leave_i_unchanged
+        )?;
+        writeln!(f, "  wrap:                     {}", q.wrap)?;
+        writeln!(f, "  jump:                     {}", q.jump)?;
+        writeln!(f, "  vblank:                   {}", q.vblank)?;
+        write!(f, "  logic:                    {}", q.logic)?;
+
+        if !self.keys.is_empty() {
+            let mut keys: Vec<_> = self.keys.iter().collect();
+            keys.sort();
+            writeln!(f)?;
+            write!(f, "Keys:")?;
+            for (action, key) in keys {
+                write!(f, " {}={:X}", action, key)?;
+            }
+        }
+
+        if let Some(colors) = &self.colors {
+            writeln!(f)?;
+            write!(f, "Colors:   {}", colors.pixels.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(sha1(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn test_lookup_returns_none_for_unknown_rom() {
+        assert_eq!(lookup(&[0x12, 0x00]), None);
+    }
+
+    #[test]
+    fn test_lookup_hash_with_platform_defaults() {
+        let info = lookup_hash("ea9af3c09b0d9e265fcd92bcc5d51a2939fdf27a").unwrap();
+
+        assert_eq!(info.title, "15 Puzzle");
+        assert_eq!(info.platform.as_deref(), Some("Cosmac VIP CHIP-8"));
+        assert_eq!(info.tickrate, Some(15));
+        assert_eq!(
+            info.quirks,
+            Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: true,
+                logic: true,
+            }
+        );
+        assert!(info.keys.is_empty());
+        assert_eq!(info.colors, None);
+    }
+
+    #[test]
+    fn test_lookup_hash_with_rom_settings() {
+        let hashes: HashMap<String, usize> = serde_json::from_str(HASHES).unwrap();
+        let programs: Vec<Program> = serde_json::from_str(PROGRAMS).unwrap();
+
+        // any ROM with its own tickrate, keys and colors
+        let (hash, rom) = programs
+            .iter()
+            .flat_map(|program| program.roms.iter())
+            .find(|(_, rom)| rom.tickrate.is_some() && !rom.keys.is_empty() && rom.colors.is_some())
+            .unwrap();
+        assert!(hashes.contains_key(hash));
+
+        let info = lookup_hash(hash).unwrap();
+
+        assert_eq!(info.tickrate, rom.tickrate);
+        assert_eq!(info.keys, rom.keys);
+        assert_eq!(info.colors, rom.colors);
+    }
+
+    #[test]
+    fn test_lookup_hash_with_quirky_platform() {
+        let info = lookup_hash("ac621d9fcada302ba6965768229ef130630bc525").unwrap();
+
+        assert_eq!(info.platform.as_deref(), Some("Cosmac VIP CHIP-8"));
+        assert!(info.quirks.memory_leave_i_unchanged);
+        assert!(info.quirks.vblank);
+    }
+}
@@ -0,0 +1,488 @@
+use std::mem;
+use std::ops::AddAssign;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread::Scope;
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+use crate::cpu::{Snapshot, CPU};
+use crate::error::Error;
+use crate::framebuffer::Framebuffer;
+use crate::instruction::Key;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Input for the CPU, from the main thread
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    Key(Key, bool),
+    Pause(bool),
+    // while paused, run a timer tick, or a single instruction
+    AdvanceFrame,
+    Step,
+    // how many times faster than real time to run
+    Speed(u32),
+    // reset the CPU and load a ROM again
+    Reset(Vec<u8>),
+    // whether frames carry a snapshot of the machine, for the debug views
+    Debug(bool),
+}
+
+/// The state of the CPU shown in a frame
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub framebuffer: Framebuffer,
+    pub screen_generation: u64,
+    pub is_buzzing: bool,
+    // for the debug views, while one is open
+    pub machine: Option<Snapshot>,
+    // run since the previous frame
+    pub progress: Progress,
+}
+
+impl Frame {
+    fn new(cpu: &CPU, progress: Progress, is_debugging: bool) -> Self {
+        Self {
+            framebuffer: *cpu.visual_buffer(),
+            screen_generation: cpu.screen_generation(),
+            is_buzzing: cpu.is_buzzing(),
+            machine: is_debugging.then(|| cpu.snapshot()),
+            progress,
+        }
+    }
+}
+
+/// What was run in a number of timer ticks
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Progress {
+    pub ticks: u32,
+    // instructions run
+    pub cycles: u32,
+    // timer ticks the buzzer sounded for
+    pub sound_ticks: u32,
+}
+
+impl AddAssign for Progress {
+    fn add_assign(&mut self, other: Self) {
+        self.ticks += other.ticks;
+        self.cycles += other.cycles;
+        self.sound_ticks += other.sound_ticks;
+    }
+}
+
+/// Runs the CPU in step with real time, either in the main thread between
+/// frames, or in a thread of its own so a slow frame doesn't stall it.
+pub enum Emulation<'a> {
+    Local(Runner<'a>),
+    Threaded {
+        commands: Sender<Command>,
+        frames: Receiver<Result<Frame>>,
+        // the last frame received
+        frame: Box<Frame>,
+    },
+}
+
+impl<'a> Emulation<'a> {
+    pub fn local(cpu: &'a mut CPU, ips: u32) -> Self {
+        Self::Local(Runner::new(cpu, ips))
+    }
+
+    /// Moves the CPU to a thread, which runs until the `Emulation` is dropped
+    /// or the CPU fails
+    pub fn spawn<'scope>(scope: &'scope Scope<'scope, '_>, cpu: &'scope mut CPU, ips: u32) -> Self {
+        let (commands, commands_rx) = mpsc::channel();
+        let (frames_tx, frames) = mpsc::channel();
+        let frame = Box::new(Frame::new(cpu, Progress::default(), false));
+
+        scope.spawn(move || run_thread(Runner::new(cpu, ips), commands_rx, frames_tx));
+
+        Self::Threaded {
+            commands,
+            frames,
+            frame,
+        }
+    }
+
+    /// Applies a command, where the errors of a CPU running in a thread are
+    /// reported in the next frame instead
+    pub fn send(&mut self, command: Command) -> Result<()> {
+        match self {
+            Self::Local(runner) => runner.apply(command),
+            Self::Threaded { commands, .. } => {
+                let _ = commands.send(command);
+                Ok(())
+            }
+        }
+    }
+
+    /// The screen as of the last frame
+    pub fn framebuffer(&self) -> Framebuffer {
+        match self {
+            Self::Local(runner) => *runner.cpu.visual_buffer(),
+            Self::Threaded { frame, .. } => frame.framebuffer,
+        }
+    }
+
+    /// Catches up with real time, returning the state of the CPU to show
+    pub fn frame(&mut self) -> Result<Frame> {
+        match self {
+            Self::Local(runner) => {
+                let progress = runner.update(Instant::now())?;
+                Ok(runner.frame(progress))
+            }
+            Self::Threaded { frames, frame, .. } => {
+                let mut progress = Progress::default();
+                loop {
+                    match frames.try_recv() {
+                        Ok(received) => {
+                            **frame = received?;
+                            progress += frame.progress;
+                        }
+                        Err(TryRecvError::Empty) => break,
+                        // after sending an error, or if it panicked
+                        Err(TryRecvError::Disconnected) => {
+                            return Err(Error::SystemError("The CPU thread stopped".to_string()))
+                        }
+                    }
+                }
+
+                Ok(Frame {
+                    progress,
+                    ..(**frame).clone()
+                })
+            }
+        }
+    }
+}
+
+/// Runs the CPU in step with real time, as told by the commands
+pub struct Runner<'a> {
+    cpu: &'a mut CPU,
+    clock: Clock,
+    // none after resuming, so the time spent paused isn't caught up with
+    last_update: Option<Instant>,
+    is_paused: bool,
+    is_debugging: bool,
+    speed: u32,
+    // run by commands since the last update
+    pending: Progress,
+}
+
+impl<'a> Runner<'a> {
+    fn new(cpu: &'a mut CPU, ips: u32) -> Self {
+        Self {
+            cpu,
+            clock: Clock::new(ips),
+            last_update: Some(Instant::now()),
+            is_paused: false,
+            is_debugging: false,
+            speed: 1,
+            pending: Progress::default(),
+        }
+    }
+
+    fn apply(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Key(key, status) => self.cpu.set_key_status(key, status),
+            Command::Pause(is_paused) => {
+                if self.is_paused && !is_paused {
+                    self.last_update = None;
+                }
+                self.is_paused = is_paused;
+            }
+            Command::AdvanceFrame => {
+                self.pending += run_timer_ticks(self.cpu, &mut self.clock, 1)?;
+            }
+            Command::Step => {
+                self.cpu.tick()?;
+                self.pending.cycles += 1;
+            }
+            Command::Speed(speed) => self.speed = speed.max(1),
+            Command::Reset(rom) => {
+                self.cpu.reset();
+                self.cpu.load_rom(&rom)?;
+            }
+            Command::Debug(is_debugging) => self.is_debugging = is_debugging,
+        }
+
+        Ok(())
+    }
+
+    /// Runs the timer ticks due by `now`, returning what was run since the
+    /// last update
+    fn update(&mut self, now: Instant) -> Result<Progress> {
+        let elapsed = self
+            .last_update
+            .map_or(Duration::ZERO, |last_update| now - last_update);
+        self.last_update = Some(now);
+
+        if !self.is_paused {
+            // the elapsed time is capped before it is sped up
+            let ticks = (0..self.speed).map(|_| self.clock.advance(elapsed)).sum();
+            self.pending += run_timer_ticks(self.cpu, &mut self.clock, ticks)?;
+        }
+
+        Ok(mem::take(&mut self.pending))
+    }
+
+    // the time left until the next timer tick, unless paused
+    fn until_next_tick(&self) -> Option<Duration> {
+        (!self.is_paused).then(|| self.clock.until_next_tick() / self.speed)
+    }
+
+    fn frame(&self, progress: Progress) -> Frame {
+        Frame::new(self.cpu, progress, self.is_debugging)
+    }
+}
+
+/// Runs `ticks` timer ticks, with the instructions due before each one
+pub fn run_timer_ticks(cpu: &mut CPU, clock: &mut Clock, ticks: u32) -> Result<Progress> {
+    let mut progress = Progress::default();
+    for _ in 0..ticks {
+        let due = clock.next_cycles();
+        for _ in 0..due {
+            cpu.tick()?;
+        }
+        // the buzzer sounds for the whole tick once the sound timer is set
+        if cpu.is_buzzing() {
+            progress.sound_ticks += 1;
+        }
+        cpu.tick_timers();
+        progress.ticks += 1;
+        progress.cycles += due;
+    }
+
+    Ok(progress)
+}
+
+fn run_thread(mut runner: Runner, commands: Receiver<Command>, frames: Sender<Result<Frame>>) {
+    loop {
+        // sleep until the next timer tick, waking up for commands
+        let received = match runner.until_next_tick() {
+            Some(timeout) => commands.recv_timeout(timeout),
+            None => commands.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let command = match received {
+            Ok(command) => Some(command),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+
+        let is_command = command.is_some();
+        let frame = command
+            .map_or(Ok(()), |command| runner.apply(command))
+            .and_then(|_| runner.update(Instant::now()))
+            .map(|progress| runner.frame(progress));
+        if matches!(&frame, Ok(frame) if !is_command && frame.progress.ticks == 0) {
+            continue;
+        }
+
+        let has_failed = frame.is_err();
+        if frames.send(frame).is_err() || has_failed {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+    use std::thread;
+    use std::time::Duration;
+
+    // draws a digit once key 0 is pressed
+    #[rustfmt::skip]
+    const DRAW_ON_KEY: [u8; 10] = [
+        0xE0, 0x9E, // skip if key V0 is pressed
+        0x12, 0x00, // jump back to 0x200
+        0xF0, 0x29, // I = digit V0
+        0xD1, 0x15, // draw
+        0x12, 0x08, // loop forever
+    ];
+
+    fn pressed(key: u8) -> Command {
+        Command::Key(Key::try_from(key).unwrap(), true)
+    }
+
+    fn any_cpu_with_rom(rom: &[u8]) -> CPU {
+        let mut cpu = CPU::new(StepRng::new(1, 1));
+        cpu.load_rom(rom).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn test_local_emulation_applies_commands() {
+        let mut cpu = any_cpu_with_rom(&DRAW_ON_KEY);
+        let mut emulation = Emulation::local(&mut cpu, 600);
+
+        emulation.send(Command::Pause(true)).unwrap();
+        emulation.send(pressed(0x0)).unwrap();
+        emulation.send(Command::AdvanceFrame).unwrap();
+        let frame = emulation.frame().unwrap();
+
+        assert_eq!(frame.progress.ticks, 1);
+        assert_eq!(frame.progress.cycles, 10);
+        assert_eq!(frame.screen_generation, 1);
+    }
+
+    #[test]
+    fn test_run_timer_ticks_counts_sound_ticks() {
+        // V0 = 2, ST = V0, then loop forever
+        let mut cpu = any_cpu_with_rom(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04]);
+        let mut clock = Clock::new(120);
+
+        let progress = run_timer_ticks(&mut cpu, &mut clock, 5).unwrap();
+
+        assert_eq!(
+            progress,
+            Progress {
+                ticks: 5,
+                cycles: 10,
+                sound_ticks: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_runner_keeps_up_with_real_time() {
+        let mut cpu = any_cpu_with_rom(&DRAW_ON_KEY);
+        let mut runner = Runner::new(&mut cpu, 600);
+        let start = runner.last_update.unwrap();
+
+        let progress = runner.update(start + Duration::from_millis(100)).unwrap();
+
+        assert_eq!(progress.ticks, 6);
+        assert_eq!(progress.cycles, 60);
+    }
+
+    #[test]
+    fn test_runner_runs_faster_at_higher_speeds() {
+        let mut cpu = any_cpu_with_rom(&DRAW_ON_KEY);
+        let mut runner = Runner::new(&mut cpu, 600);
+        let start = runner.last_update.unwrap();
+
+        runner.apply(Command::Speed(3)).unwrap();
+        let progress = runner.update(start + Duration::from_millis(100)).unwrap();
+
+        assert_eq!(progress.ticks, 18);
+    }
+
+    #[test]
+    fn test_paused_runner_only_runs_when_told() {
+        let mut cpu = any_cpu_with_rom(&DRAW_ON_KEY);
+        let mut runner = Runner::new(&mut cpu, 600);
+        let start = runner.last_update.unwrap();
+
+        runner.apply(Command::Pause(true)).unwrap();
+        assert_eq!(runner.until_next_tick(), None);
+        let progress = runner.update(start + Duration::from_secs(1)).unwrap();
+        assert_eq!(progress, Progress::default());
+
+        runner.apply(Command::AdvanceFrame).unwrap();
+        runner.apply(Command::Step).unwrap();
+        let progress = runner.update(start + Duration::from_secs(2)).unwrap();
+        assert_eq!(
+            progress,
+            Progress {
+                ticks: 1,
+                cycles: 11,
+                sound_ticks: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_runner_resumes_where_it_paused() {
+        let mut cpu = any_cpu_with_rom(&DRAW_ON_KEY);
+        let mut runner = Runner::new(&mut cpu, 600);
+        let start = runner.last_update.unwrap();
+
+        // like the CPU thread, which doesn't update while paused
+        runner.apply(Command::Pause(true)).unwrap();
+        runner.apply(Command::Pause(false)).unwrap();
+        let progress = runner.update(start + Duration::from_secs(1)).unwrap();
+        assert_eq!(progress, Progress::default());
+
+        let progress = runner.update(start + Duration::from_millis(1100)).unwrap();
+        assert_eq!(progress.ticks, 6);
+    }
+
+    #[test]
+    fn test_runner_resets_the_cpu() {
+        let mut cpu = any_cpu_with_rom(&DRAW_ON_KEY);
+        let mut runner = Runner::new(&mut cpu, 600);
+        let start = runner.last_update.unwrap();
+        runner.apply(pressed(0x0)).unwrap();
+        runner.update(start + Duration::from_millis(100)).unwrap();
+        assert!(runner
+            .frame(Progress::default())
+            .framebuffer
+            .pixels()
+            .any(|is_on| is_on));
+
+        runner.apply(Command::Reset(DRAW_ON_KEY.to_vec())).unwrap();
+        runner.update(start + Duration::from_millis(200)).unwrap();
+
+        let frame = runner.frame(Progress::default());
+        assert_eq!(frame.framebuffer, Framebuffer::default());
+    }
+
+    #[test]
+    fn test_runner_snapshots_the_machine_while_debugging() {
+        let mut cpu = any_cpu_with_rom(&DRAW_ON_KEY);
+        let mut runner = Runner::new(&mut cpu, 600);
+        assert_eq!(runner.frame(Progress::default()).machine, None);
+
+        runner.apply(Command::Debug(true)).unwrap();
+        let machine = runner.frame(Progress::default()).machine;
+        assert_eq!(machine.map(|machine| machine.pc), Some(0x200));
+
+        runner.apply(Command::Debug(false)).unwrap();
+        assert_eq!(runner.frame(Progress::default()).machine, None);
+    }
+
+    #[test]
+    fn test_threaded_emulation_sends_frames() {
+        let mut cpu = any_cpu_with_rom(&DRAW_ON_KEY);
+
+        thread::scope(|scope| {
+            let mut emulation = Emulation::spawn(scope, &mut cpu, 600);
+            emulation.send(pressed(0x0)).unwrap();
+
+            let mut frame = emulation.frame().unwrap();
+            for _ in 0..50 {
+                if frame.screen_generation > 0 {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+                frame = emulation.frame().unwrap();
+            }
+
+            assert_eq!(frame.screen_generation, 1);
+            assert!(frame.framebuffer.pixels().any(|is_on| is_on));
+        });
+    }
+
+    #[test]
+    fn test_threaded_emulation_reports_errors() {
+        let mut cpu = any_cpu_with_rom(&[0xFF, 0xFF]);
+
+        thread::scope(|scope| {
+            let mut emulation = Emulation::spawn(scope, &mut cpu, 600);
+
+            let mut res = emulation.frame();
+            for _ in 0..50 {
+                if res.is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+                res = emulation.frame();
+            }
+
+            assert!(matches!(res, Err(Error::RuntimeError(_))));
+            // the thread has stopped
+            assert!(matches!(emulation.frame(), Err(Error::SystemError(_))));
+        });
+    }
+}
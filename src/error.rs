@@ -7,16 +7,18 @@ use std::error::Error as ErrorTrait;
 use std::io;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     IOError(io::Error),
     RuntimeError(CPUError),
     SystemError(String),
+    ConfigError(String),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::SystemError(msg) => write!(f, "{}", msg),
+            Self::SystemError(msg) | Self::ConfigError(msg) => write!(f, "{}", msg),
             _ => match self.source() {
                 Some(err) => write!(f, "{}", err),
                 None => write!(f, "{:?}", self),
@@ -47,6 +49,35 @@ impl From<CPUError> for Error {
     }
 }
 
+/// Converts the errors SDL reports as strings
+pub fn to_sdl_err(err: String) -> Error {
+    Error::SystemError(err)
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Error {
+        Error::ConfigError(format!("{}", err))
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Error {
+        Error::ConfigError(format!("{}", err))
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(err: png::EncodingError) -> Error {
+        Error::SystemError(format!("{}", err))
+    }
+}
+
+impl From<gif::EncodingError> for Error {
+    fn from(err: gif::EncodingError) -> Error {
+        Error::SystemError(format!("{}", err))
+    }
+}
+
 impl From<WindowBuildError> for Error {
     fn from(err: WindowBuildError) -> Error {
         Error::SystemError(format!("{}", err))
@@ -77,7 +108,6 @@ pub enum CPUError {
     StackOverflow,
     InvalidOpcode(u16),
     InvalidAddress(u16),
-    InvalidVRegister(u8),
     InvalidKey(usize),
     InvalidDigit(u8),
 }
@@ -90,7 +120,6 @@ impl fmt::Display for CPUError {
             Self::InvalidKey(key) => write!(f, "Invalid key: {}", key),
             Self::InvalidOpcode(op) => write!(f, "Invalid opcode: {:#04X}", op),
             Self::InvalidAddress(addr) => write!(f, "Invalid memory address: {:#04X}", addr),
-            Self::InvalidVRegister(i) => write!(f, "Invalid V-Register: {:#01X}", i),
             Self::InvalidDigit(x) => write!(f, "Invalid digit: {}", x),
         }
     }
@@ -0,0 +1,411 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// An RGB pixel
+pub type Rgb = [u8; 3];
+
+// brightness of the dimmed rows and color channels of the CRT filters, out of 255
+const SCANLINE_LEVEL: u16 = 128;
+const MASK_LEVEL: u16 = 180;
+// largest differences in luma and chroma between colors hq2x considers alike
+const Y_THRESHOLD: i32 = 48;
+const U_THRESHOLD: i32 = 7;
+const V_THRESHOLD: i32 = 6;
+
+/// Pixel-art upscaling filters, applied to the screen before it's scaled by
+/// the window.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    #[default]
+    None,
+    // a.k.a. EPX
+    Scale2x,
+    Scale3x,
+    // hq2x, interpolating the colors along edges
+    Hqx,
+    // dark gaps between the rows of pixels
+    Scanlines,
+    // scanlines and an aperture grille mask
+    Crt,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "scale2x" | "epx" => Ok(Self::Scale2x),
+            "scale3x" => Ok(Self::Scale3x),
+            "hqx" | "hq2x" => Ok(Self::Hqx),
+            "scanlines" => Ok(Self::Scanlines),
+            "crt" => Ok(Self::Crt),
+            _ => Err(format!(
+                "unknown filter: {} (none, scale2x, scale3x, hqx, scanlines, crt)",
+                s
+            )),
+        }
+    }
+}
+
+impl Filter {
+    /// How many times larger than the source the output of the filter is
+    pub fn factor(&self) -> usize {
+        match self {
+            Self::None => 1,
+            Self::Scale2x | Self::Hqx => 2,
+            Self::Scale3x | Self::Scanlines | Self::Crt => 3,
+        }
+    }
+
+    /// Filters a `width` x `height` image, given row by row
+    pub fn apply(&self, src: &[Rgb], width: usize, height: usize) -> Vec<Rgb> {
+        assert_eq!(src.len(), width * height, "Invalid image size");
+
+        let image = Image { src, width, height };
+        match self {
+            Self::None => src.to_vec(),
+            Self::Scale2x => scale2x(&image),
+            Self::Hqx => hq2x(&image),
+            Self::Scale3x => scale3x(&image),
+            Self::Scanlines => crt(&image, false),
+            Self::Crt => crt(&image, true),
+        }
+    }
+}
+
+struct Image<'a> {
+    src: &'a [Rgb],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Image<'a> {
+    /// The pixel at (x + dx, y + dy), where pixels past the edges repeat the
+    /// ones on the edges
+    fn at(&self, x: usize, y: usize, dx: isize, dy: isize) -> Rgb {
+        let x = x.saturating_add_signed(dx).min(self.width - 1);
+        let y = y.saturating_add_signed(dy).min(self.height - 1);
+        self.src[y * self.width + x]
+    }
+
+    /// Builds a `factor` times larger image, given the block of output
+    /// pixels for each source pixel
+    fn upscale<const N: usize>(
+        &self,
+        factor: usize,
+        block: impl Fn(usize, usize) -> [Rgb; N],
+    ) -> Vec<Rgb> {
+        let out_width = self.width * factor;
+        let mut out = vec![[0; 3]; self.src.len() * N];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                for (i, pixel) in block(x, y).into_iter().enumerate() {
+                    let (out_x, out_y) = (x * factor + i % factor, y * factor + i / factor);
+                    out[out_y * out_width + out_x] = pixel;
+                }
+            }
+        }
+
+        out
+    }
+}
+
+// Each pixel P becomes four, taking the color of two equal neighbours
+// forming a corner:
+//
+//   A        E0 E1
+// C P B  ->  E2 E3
+//   D
+fn scale2x(image: &Image) -> Vec<Rgb> {
+    image.upscale(2, |x, y| {
+        let p = image.at(x, y, 0, 0);
+        let a = image.at(x, y, 0, -1);
+        let b = image.at(x, y, 1, 0);
+        let c = image.at(x, y, -1, 0);
+        let d = image.at(x, y, 0, 1);
+
+        let pick = |is_edge: bool, neighbour: Rgb| if is_edge { neighbour } else { p };
+        [
+            pick(c == a && c != d && a != b, a),
+            pick(a == b && a != c && b != d, b),
+            pick(d == c && d != b && c != a, c),
+            pick(b == d && b != a && d != c, d),
+        ]
+    })
+}
+
+// Each pixel E becomes nine, following the rules of Scale3x:
+//
+// A B C      E0 E1 E2
+// D E F  ->  E3 E4 E5
+// G H I      E6 E7 E8
+fn scale3x(image: &Image) -> Vec<Rgb> {
+    image.upscale(3, |x, y| {
+        let at = |dx, dy| image.at(x, y, dx, dy);
+        let (a, b, c) = (at(-1, -1), at(0, -1), at(1, -1));
+        let (d, e, f) = (at(-1, 0), at(0, 0), at(1, 0));
+        let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+
+        if b == h || d == f {
+            return [e; 9];
+        }
+
+        let pick = |is_edge: bool, neighbour: Rgb| if is_edge { neighbour } else { e };
+        [
+            pick(d == b, d),
+            pick((d == b && e != c) || (b == f && e != a), b),
+            pick(b == f, f),
+            pick((d == b && e != g) || (d == h && e != a), d),
+            e,
+            pick((b == f && e != i) || (h == f && e != c), f),
+            pick(d == h, d),
+            pick((d == h && e != i) || (h == f && e != g), h),
+            pick(h == f, f),
+        ]
+    })
+}
+
+// Each pixel E becomes four, each one interpolated from E and the three
+// neighbours on its side, following the 3x3 pattern of neighbours that differ
+// from E in the way of hq2x. For E0:
+//
+// C A        E0 E1
+// B E   ->   E2 E3
+//
+// - an edge crosses the corner when A and B are alike and both differ from
+//   E: E0 blends E with A and B, more so when C is on their side too
+// - a corner that only touches C is blended with it a little
+// - otherwise E0 keeps the color of E
+fn hq2x(image: &Image) -> Vec<Rgb> {
+    image.upscale(2, |x, y| {
+        let e = image.at(x, y, 0, 0);
+
+        [(-1, -1), (1, -1), (-1, 1), (1, 1)].map(|(dx, dy)| {
+            let a = image.at(x, y, 0, dy);
+            let b = image.at(x, y, dx, 0);
+            let c = image.at(x, y, dx, dy);
+
+            let is_edge = is_alike(a, b) && !is_alike(e, a) && !is_alike(e, b);
+            if is_edge && !is_alike(e, c) {
+                blend(&[(e, 2), (a, 1), (b, 1)])
+            } else if is_edge {
+                blend(&[(e, 6), (a, 1), (b, 1)])
+            } else if !is_alike(e, c) && is_alike(e, a) && is_alike(e, b) {
+                blend(&[(e, 3), (c, 1)])
+            } else {
+                e
+            }
+        })
+    })
+}
+
+// Each pixel becomes a 3x3 block with a dimmed bottom row, and optionally a
+// column for each of the red, green and blue phosphors
+fn crt(image: &Image, has_mask: bool) -> Vec<Rgb> {
+    image.upscale(3, |x, y| {
+        let p = image.at(x, y, 0, 0);
+
+        let mut block = [p; 9];
+        for (i, pixel) in block.iter_mut().enumerate() {
+            let (column, row) = (i % 3, i / 3);
+            if has_mask {
+                for (channel, value) in pixel.iter_mut().enumerate() {
+                    if channel != column {
+                        *value = dim(*value, MASK_LEVEL);
+                    }
+                }
+            }
+            if row == 2 {
+                *pixel = pixel.map(|value| dim(value, SCANLINE_LEVEL));
+            }
+        }
+
+        block
+    })
+}
+
+// whether two colors are close in YUV, like hq2x compares them
+fn is_alike(a: Rgb, b: Rgb) -> bool {
+    let (ya, ua, va) = yuv(a);
+    let (yb, ub, vb) = yuv(b);
+    (ya - yb).abs() <= Y_THRESHOLD
+        && (ua - ub).abs() <= U_THRESHOLD
+        && (va - vb).abs() <= V_THRESHOLD
+}
+
+fn yuv([r, g, b]: Rgb) -> (i32, i32, i32) {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    (
+        (r + g + b) / 3,
+        128 + (r - b) / 4,
+        128 + (2 * g - r - b) / 8,
+    )
+}
+
+// the average of colors, given with their weights
+fn blend(colors: &[(Rgb, u16)]) -> Rgb {
+    let total: u16 = colors.iter().map(|(_, weight)| weight).sum();
+    [0, 1, 2].map(|i| {
+        let sum: u16 = colors
+            .iter()
+            .map(|(color, weight)| color[i] as u16 * weight)
+            .sum();
+        (sum / total) as u8
+    })
+}
+
+fn dim(value: u8, level: u16) -> u8 {
+    (value as u16 * level / 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const O: Rgb = [0, 0, 0];
+    const X: Rgb = [255, 255, 255];
+
+    // a diagonal line, from the top left corner
+    const DIAGONAL: [Rgb; 9] = [
+        X, O, O, //
+        O, X, O, //
+        O, O, X, //
+    ];
+
+    #[test]
+    fn test_factor_matches_output_size() {
+        for filter in [
+            Filter::None,
+            Filter::Scale2x,
+            Filter::Scale3x,
+            Filter::Hqx,
+            Filter::Scanlines,
+            Filter::Crt,
+        ] {
+            let out = filter.apply(&DIAGONAL, 3, 3);
+            assert_eq!(out.len(), 9 * filter.factor() * filter.factor());
+        }
+    }
+
+    #[test]
+    fn test_none_returns_the_same_image() {
+        assert_eq!(Filter::None.apply(&DIAGONAL, 3, 3), DIAGONAL);
+    }
+
+    #[test]
+    fn test_scale2x_of_a_flat_image_is_flat() {
+        assert_eq!(Filter::Scale2x.apply(&[X; 4], 2, 2), [X; 16]);
+    }
+
+    #[test]
+    fn test_scale2x_smooths_diagonals() {
+        let out = Filter::Scale2x.apply(&DIAGONAL, 3, 3);
+
+        #[rustfmt::skip]
+        assert_eq!(out, [
+            X, X, O, O, O, O,
+            X, O, X, O, O, O,
+            O, X, X, X, O, O,
+            O, O, X, X, X, O,
+            O, O, O, X, O, X,
+            O, O, O, O, X, X,
+        ]);
+    }
+
+    #[test]
+    fn test_scale2x_keeps_single_pixels() {
+        let image = [O, O, O, O, X, O, O, O, O];
+
+        let out = Filter::Scale2x.apply(&image, 3, 3);
+
+        assert_eq!(out.iter().filter(|pixel| **pixel == X).count(), 4);
+    }
+
+    #[test]
+    fn test_scale3x_smooths_diagonals() {
+        let out = Filter::Scale3x.apply(&DIAGONAL, 3, 3);
+
+        #[rustfmt::skip]
+        assert_eq!(out, [
+            X, X, X, O, O, O, O, O, O,
+            X, X, O, X, O, O, O, O, O,
+            X, O, O, X, O, O, O, O, O,
+            O, X, X, X, X, X, O, O, O,
+            O, O, O, X, X, X, O, O, O,
+            O, O, O, X, X, X, X, X, O,
+            O, O, O, O, O, X, O, O, X,
+            O, O, O, O, O, X, O, X, X,
+            O, O, O, O, O, O, X, X, X,
+        ]);
+    }
+
+    #[test]
+    fn test_hqx_of_a_flat_image_is_flat() {
+        assert_eq!(Filter::Hqx.apply(&[X; 4], 2, 2), [X; 16]);
+    }
+
+    #[test]
+    fn test_hqx_interpolates_diagonals() {
+        // X blended with O on both sides of the corner, and the other way
+        // round: 6:1:1 when the diagonal is on E's side, 2:1:1 otherwise
+        const L: Rgb = [191, 191, 191];
+        const M: Rgb = [127, 127, 127];
+        // O blended with X on both sides, or with an X diagonal only
+        const S: Rgb = [63, 63, 63];
+
+        let out = Filter::Hqx.apply(&DIAGONAL, 3, 3);
+
+        #[rustfmt::skip]
+        assert_eq!(out, [
+            X, X, O, O, O, O,
+            X, L, S, O, S, O,
+            O, S, L, M, O, O,
+            O, O, M, L, S, O,
+            O, S, O, S, L, X,
+            O, O, O, O, X, X,
+        ]);
+    }
+
+    #[test]
+    fn test_hqx_treats_close_colors_alike() {
+        let image = [[0, 0, 0], [20, 20, 20], [0, 0, 0], [20, 20, 20]];
+
+        let out = Filter::Hqx.apply(&image, 2, 2);
+
+        // no edges, so each pixel is only made larger
+        assert_eq!(
+            out[0..4],
+            [[0, 0, 0], [0, 0, 0], [20, 20, 20], [20, 20, 20]]
+        );
+    }
+
+    #[test]
+    fn test_scanlines_dim_the_bottom_row_of_each_pixel() {
+        let out = Filter::Scanlines.apply(&[X], 1, 1);
+
+        assert_eq!(&out[0..6], [X; 6]);
+        assert_eq!(&out[6..9], [[128, 128, 128]; 3]);
+    }
+
+    #[test]
+    fn test_crt_masks_color_channels() {
+        let out = Filter::Crt.apply(&[X], 1, 1);
+
+        assert_eq!(
+            &out[0..3],
+            [[255, 180, 180], [180, 255, 180], [180, 180, 255]]
+        );
+        assert_eq!(out[6], [128, 90, 90]);
+    }
+
+    #[test]
+    fn test_filter_from_str() {
+        assert_eq!("epx".parse(), Ok(Filter::Scale2x));
+        assert_eq!("crt".parse(), Ok(Filter::Crt));
+        assert!("blur".parse::<Filter>().is_err());
+    }
+}
@@ -0,0 +1,144 @@
+const SCREEN_WIDTH: usize = 64;
+const SCREEN_HEIGHT: usize = 32;
+
+// a row of pixels, with the leftmost pixel in the most significant bit
+type Row = u64;
+const ROW_BITS: u32 = Row::BITS;
+
+/// The CHIP-8 screen, packed as a row of bits per line so sprites are drawn
+/// with a few shifts and XORs per row.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Framebuffer {
+    rows: [Row; SCREEN_HEIGHT],
+}
+
+impl From<[Row; SCREEN_HEIGHT]> for Framebuffer {
+    fn from(rows: [Row; SCREEN_HEIGHT]) -> Self {
+        Self { rows }
+    }
+}
+
+impl Framebuffer {
+    pub fn clear(&mut self) {
+        self.rows = [0; SCREEN_HEIGHT];
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & (1 << (ROW_BITS as usize - 1 - x)) != 0
+    }
+
+    /// All the pixels of the screen, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..SCREEN_HEIGHT).flat_map(move |y| (0..SCREEN_WIDTH).map(move |x| self.pixel(x, y)))
+    }
+
+    /// XORs a sprite onto the screen, returning whether any pixel was turned
+    /// off. The starting position always wraps, but the pixels that fall
+    /// outside the screen are clipped unless `wrap` is set.
+    pub fn draw_sprite(&mut self, sprite: &[u8], x: usize, y: usize, wrap: bool) -> bool {
+        let x = (x % SCREEN_WIDTH) as u32;
+        let y = y % SCREEN_HEIGHT;
+        let mut did_collide = false;
+
+        for (i, byte) in sprite.iter().enumerate() {
+            if !wrap && y + i >= SCREEN_HEIGHT {
+                break;
+            }
+
+            let line = (*byte as Row) << (ROW_BITS - 8);
+            let line = if wrap {
+                line.rotate_right(x)
+            } else {
+                line >> x
+            };
+
+            let row = &mut self.rows[(y + i) % SCREEN_HEIGHT];
+            did_collide |= *row & line != 0;
+            *row ^= line;
+        }
+
+        did_collide
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(framebuffer: &Framebuffer, y: usize) -> Vec<bool> {
+        (0..SCREEN_WIDTH).map(|x| framebuffer.pixel(x, y)).collect()
+    }
+
+    #[test]
+    fn test_draw_sprite() {
+        let mut framebuffer = Framebuffer::default();
+
+        let did_collide = framebuffer.draw_sprite(&[0b1010_0000, 0b0000_0001], 2, 1, true);
+
+        assert!(!did_collide);
+        assert_eq!(
+            framebuffer.pixels().filter(|is_on| *is_on).count(),
+            3,
+            "Only the sprite pixels are lit"
+        );
+        assert!(framebuffer.pixel(2, 1));
+        assert!(framebuffer.pixel(4, 1));
+        assert!(framebuffer.pixel(9, 2));
+    }
+
+    #[test]
+    fn test_draw_sprite_wraps() {
+        let mut framebuffer = Framebuffer::default();
+
+        framebuffer.draw_sprite(&[0xFF, 0xFF], 60, 31, true);
+
+        let mut expected = vec![false; SCREEN_WIDTH];
+        expected[0..4].fill(true);
+        expected[60..64].fill(true);
+        assert_eq!(row(&framebuffer, 31), expected);
+        assert_eq!(row(&framebuffer, 0), expected);
+    }
+
+    #[test]
+    fn test_draw_sprite_clips() {
+        let mut framebuffer = Framebuffer::default();
+
+        framebuffer.draw_sprite(&[0xFF, 0xFF], 60, 31, false);
+
+        let mut expected = vec![false; SCREEN_WIDTH];
+        expected[60..64].fill(true);
+        assert_eq!(row(&framebuffer, 31), expected);
+        assert_eq!(row(&framebuffer, 0), [false; SCREEN_WIDTH]);
+    }
+
+    #[test]
+    fn test_draw_sprite_wraps_the_starting_position() {
+        let mut framebuffer = Framebuffer::default();
+
+        framebuffer.draw_sprite(&[0x80], 65, 33, false);
+
+        assert!(framebuffer.pixel(1, 1));
+    }
+
+    #[test]
+    fn test_draw_sprite_detects_collisions() {
+        let mut framebuffer = Framebuffer::default();
+        framebuffer.draw_sprite(&[0x0F], 0, 0, true);
+
+        assert!(framebuffer.draw_sprite(&[0xFF], 0, 0, true));
+        assert_eq!(
+            row(&framebuffer, 0)[0..8],
+            [true, true, true, true, false, false, false, false]
+        );
+        assert!(!framebuffer.draw_sprite(&[0x0F], 0, 0, true));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut framebuffer = Framebuffer::from([Row::MAX; SCREEN_HEIGHT]);
+
+        framebuffer.clear();
+
+        assert_eq!(framebuffer, Framebuffer::default());
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use sdl2::controller::GameController;
+use sdl2::GameControllerSubsystem;
+
+use crate::error::{to_sdl_err, Error};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+const RUMBLE_LOW_FREQ: u16 = 0x4000;
+const RUMBLE_HIGH_FREQ: u16 = 0x4000;
+// long enough to last until the next frame, so the pulse is refreshed
+// while the buzzer keeps sounding
+const RUMBLE_DURATION_MS: u32 = 100;
+
+/// Keeps track of the connected game controllers.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    controllers: HashMap<u32, GameController>,
+    has_rumble: bool,
+    is_rumbling: bool,
+}
+
+impl Gamepads {
+    pub fn new(context: &sdl2::Sdl, has_rumble: bool) -> Result<Self> {
+        let subsystem = context.game_controller().map_err(to_sdl_err)?;
+
+        Ok(Self {
+            subsystem,
+            controllers: HashMap::new(),
+            has_rumble,
+            is_rumbling: false,
+        })
+    }
+
+    /// Opens a newly connected controller, given its joystick index
+    pub fn add(&mut self, joystick_index: u32) -> Result<()> {
+        let controller = self.subsystem.open(joystick_index)?;
+        self.controllers
+            .insert(controller.instance_id(), controller);
+        Ok(())
+    }
+
+    /// Closes a disconnected controller, given its instance id
+    pub fn remove(&mut self, instance_id: u32) {
+        self.controllers.remove(&instance_id);
+    }
+
+    pub fn set_buzzing(&mut self, is_buzzing: bool) {
+        if !self.has_rumble || (!is_buzzing && !self.is_rumbling) {
+            return;
+        }
+
+        let (low, high) = if is_buzzing {
+            (RUMBLE_LOW_FREQ, RUMBLE_HIGH_FREQ)
+        } else {
+            (0, 0)
+        };
+
+        for controller in self.controllers.values_mut() {
+            // not every controller supports rumble, so errors are ignored
+            let _ = controller.set_rumble(low, high, RUMBLE_DURATION_MS);
+        }
+
+        self.is_rumbling = is_buzzing;
+    }
+}
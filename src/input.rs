@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+
+use sdl2::controller::Button;
+use sdl2::keyboard::Scancode;
+
+use crate::keymap::Keymap;
+
+const KEYPAD_SIZE: usize = 16;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+enum Binding {
+    Key(Scancode),
+    // (controller instance id, button)
+    Button(u32, Button),
+}
+
+/// Translates physical key and controller button events into CHIP-8 key
+/// presses and releases.
+///
+/// A CHIP-8 key stays pressed while at least one of the physical keys or
+/// buttons bound to it is held, and auto-repeated key presses are ignored.
+pub struct Input {
+    keymap: Keymap,
+    held: HashSet<Binding>,
+    held_count: [u8; KEYPAD_SIZE],
+}
+
+impl Input {
+    pub fn new(keymap: Keymap) -> Self {
+        Self {
+            keymap,
+            held: HashSet::new(),
+            held_count: [0; KEYPAD_SIZE],
+        }
+    }
+
+    pub fn is_bound(&self, code: Scancode) -> bool {
+        self.keymap.get(&code).is_some()
+    }
+
+    /// Returns the CHIP-8 key that became pressed, if any
+    pub fn key_down(&mut self, code: Scancode, is_repeat: bool) -> Option<u8> {
+        if is_repeat {
+            return None;
+        }
+
+        let key = self.keymap.get(&code)?;
+        self.press(Binding::Key(code), key)
+    }
+
+    /// Returns the CHIP-8 key that became released, if any
+    pub fn key_up(&mut self, code: Scancode) -> Option<u8> {
+        let key = self.keymap.get(&code)?;
+        self.release(Binding::Key(code), key)
+    }
+
+    /// Returns the CHIP-8 key that became pressed, if any
+    pub fn button_down(&mut self, controller: u32, button: Button) -> Option<u8> {
+        let key = self.keymap.get_button(&button)?;
+        self.press(Binding::Button(controller, button), key)
+    }
+
+    /// Returns the CHIP-8 key that became released, if any
+    pub fn button_up(&mut self, controller: u32, button: Button) -> Option<u8> {
+        let key = self.keymap.get_button(&button)?;
+        self.release(Binding::Button(controller, button), key)
+    }
+
+    /// Releases the buttons held in a disconnected controller, returning the
+    /// CHIP-8 keys that became released
+    pub fn release_controller(&mut self, controller: u32) -> Vec<u8> {
+        let buttons: Vec<Binding> = self
+            .held
+            .iter()
+            .filter(|binding| matches!(binding, Binding::Button(id, _) if *id == controller))
+            .copied()
+            .collect();
+
+        let mut released = vec![];
+        for binding in buttons {
+            if let Binding::Button(_, button) = binding {
+                released.extend(self.button_up(controller, button));
+            }
+        }
+
+        released.sort();
+        released
+    }
+
+    /// Releases every held key (i.e. when the window loses focus and key up
+    /// events would be missed), returning the CHIP-8 keys that became released
+    pub fn release_all(&mut self) -> Vec<u8> {
+        self.held.clear();
+
+        let released = (0..KEYPAD_SIZE as u8)
+            .filter(|key| self.held_count[*key as usize] > 0)
+            .collect();
+        self.held_count = [0; KEYPAD_SIZE];
+
+        released
+    }
+
+    fn press(&mut self, binding: Binding, key: u8) -> Option<u8> {
+        if !self.held.insert(binding) {
+            return None;
+        }
+
+        let count = &mut self.held_count[key as usize];
+        *count += 1;
+
+        (*count == 1).then_some(key)
+    }
+
+    fn release(&mut self, binding: Binding, key: u8) -> Option<u8> {
+        if !self.held.remove(&binding) {
+            return None;
+        }
+
+        let count = &mut self.held_count[key as usize];
+        *count -= 1;
+
+        (*count == 0).then_some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn any_input() -> Input {
+        Input::new(Keymap::default())
+    }
+
+    #[test]
+    fn test_key_down_and_up() {
+        let mut input = any_input();
+
+        assert_eq!(input.key_down(Scancode::W, false), Some(0x5));
+        assert_eq!(input.key_up(Scancode::W), Some(0x5));
+    }
+
+    #[test]
+    fn test_unmapped_keys_are_ignored() {
+        let mut input = any_input();
+
+        assert_eq!(input.key_down(Scancode::P, false), None);
+        assert_eq!(input.key_up(Scancode::P), None);
+    }
+
+    #[test]
+    fn test_is_bound() {
+        let input = any_input();
+
+        assert!(input.is_bound(Scancode::W));
+        assert!(!input.is_bound(Scancode::P));
+    }
+
+    #[test]
+    fn test_key_stays_pressed_while_another_binding_is_held() {
+        let mut input = any_input();
+
+        assert_eq!(input.key_down(Scancode::W, false), Some(0x5));
+        assert_eq!(input.key_down(Scancode::Up, false), None);
+        assert_eq!(input.key_up(Scancode::W), None);
+        assert_eq!(input.key_up(Scancode::Up), Some(0x5));
+    }
+
+    #[test]
+    fn test_repeated_key_down_is_ignored() {
+        let mut input = any_input();
+
+        assert_eq!(input.key_down(Scancode::W, false), Some(0x5));
+        assert_eq!(input.key_down(Scancode::W, true), None);
+        assert_eq!(input.key_down(Scancode::W, false), None);
+        assert_eq!(input.key_up(Scancode::W), Some(0x5));
+    }
+
+    #[test]
+    fn test_key_up_without_key_down_is_ignored() {
+        let mut input = any_input();
+
+        assert_eq!(input.key_up(Scancode::W), None);
+    }
+
+    #[test]
+    fn test_button_down_and_up() {
+        let mut input = any_input();
+
+        assert_eq!(input.button_down(0, Button::DPadUp), Some(0x5));
+        assert_eq!(input.button_up(0, Button::DPadUp), Some(0x5));
+    }
+
+    #[test]
+    fn test_key_stays_pressed_while_a_button_is_held() {
+        let mut input = any_input();
+
+        assert_eq!(input.key_down(Scancode::W, false), Some(0x5));
+        assert_eq!(input.button_down(0, Button::DPadUp), None);
+        assert_eq!(input.button_down(1, Button::DPadUp), None);
+        assert_eq!(input.key_up(Scancode::W), None);
+        assert_eq!(input.button_up(0, Button::DPadUp), None);
+        assert_eq!(input.button_up(1, Button::DPadUp), Some(0x5));
+    }
+
+    #[test]
+    fn test_release_controller() {
+        let mut input = any_input();
+        input.button_down(0, Button::DPadUp);
+        input.button_down(0, Button::A);
+        input.button_down(1, Button::A);
+
+        assert_eq!(input.release_controller(0), vec![0x5]);
+        assert_eq!(input.button_up(1, Button::A), Some(0x6));
+    }
+
+    #[test]
+    fn test_release_all() {
+        let mut input = any_input();
+        input.key_down(Scancode::W, false);
+        input.key_down(Scancode::Up, false);
+        input.key_down(Scancode::V, false);
+
+        assert_eq!(input.release_all(), vec![0x5, 0xF]);
+        assert_eq!(input.key_up(Scancode::W), None);
+        assert_eq!(input.key_down(Scancode::W, false), Some(0x5));
+    }
+}
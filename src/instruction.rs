@@ -1,4 +1,58 @@
+use std::fmt;
+
 use crate::error::CPUError;
+use crate::quirks::Quirks;
+
+const NIBBLE_MASK: u8 = 0xF;
+
+/// A V-register, from V0 to VF
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct VReg(u8);
+
+impl VReg {
+    pub const VF: Self = Self(0xF);
+
+    /// The register named by the lowest 4 bits of `nibble`
+    pub fn new(nibble: u8) -> Self {
+        Self(nibble & NIBBLE_MASK)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The registers from V0 up to this one, both included
+    pub fn up_to(self) -> impl Iterator<Item = Self> {
+        (0..=self.0).map(Self)
+    }
+}
+
+impl fmt::Display for VReg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "V{:X}", self.0)
+    }
+}
+
+/// A key of the CHIP-8 keypad, from 0x0 to 0xF
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Key(u8);
+
+impl Key {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl TryFrom<u8> for Key {
+    type Error = CPUError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value > NIBBLE_MASK {
+            return Err(CPUError::InvalidKey(value as usize));
+        }
+        Ok(Self(value))
+    }
+}
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Instruction {
@@ -13,65 +67,65 @@ pub enum Instruction {
     // 2nnn -> Stack[SP] = PC; SP += 1; PC = nnn
     Call(u16),
     // 3xkk -> Skip next if Vx == kk
-    SkipVxEqual(u8, u8),
+    SkipVxEqual(VReg, u8),
     // 4xkk -> Skip next if Vx != kk
-    SkipVxNotEqual(u8, u8),
+    SkipVxNotEqual(VReg, u8),
     // 5xy0 -> Skip next if Vx == Vy
-    SkipEqual(u8, u8),
+    SkipEqual(VReg, VReg),
     // 6xkk -> Vx = kk
-    LoadVx(u8, u8),
+    LoadVx(VReg, u8),
     // 7xkk -> Vx += kk
-    AddVx(u8, u8),
+    AddVx(VReg, u8),
     // 8xy0 -> Vx = Vy
-    Set(u8, u8),
+    Set(VReg, VReg),
     // 8xy1 -> Vx = Vx OR Vy
-    Or(u8, u8),
+    Or(VReg, VReg),
     // 8xy2 -> Vx = Vx AND Vy
-    And(u8, u8),
+    And(VReg, VReg),
     // 8xy3 -> Vx = Vx XOR Vy
-    Xor(u8, u8),
+    Xor(VReg, VReg),
     // 8xy4 -> Vx = Vx + Vy; VF = carry
-    Add(u8, u8),
+    Add(VReg, VReg),
     // 8xy5 -> Vx = Vx - Vy; VF = NOT borrow
-    Sub(u8, u8),
-    // 8xy6 -> Vx >> 1; VF = shifted out bit
-    ShiftRightVx(u8),
+    Sub(VReg, VReg),
+    // 8xy6 -> Vx = Vx >> 1 (or Vy >> 1, depending on quirks); VF = shifted out bit
+    ShiftRightVx(VReg, VReg),
     // 8xy7 -> Vx = Vy - Vy; VF = NOT borrow
-    SubN(u8, u8),
-    // 8xyE -> Vx << 1; VF = shifted out bit
-    ShiftLeftVx(u8),
+    SubN(VReg, VReg),
+    // 8xyE -> Vx = Vx << 1 (or Vy << 1, depending on quirks); VF = shifted out bit
+    ShiftLeftVx(VReg, VReg),
     // 9xy0 -> Skip next if Vx != Vy
-    SkipNotEqual(u8, u8),
+    SkipNotEqual(VReg, VReg),
     // Annn -> I = nnn
     LoadI(u16),
     // Bxnn -> PC = xnn + Vx
-    JumpOffset(u8, u16),
+    JumpOffset(VReg, u16),
     // Cxkk -> Vx = rand() AND kk
-    Rand(u8, u8),
+    Rand(VReg, u8),
     // Dxyn -> Draw n-byte sprite starting at I at (Vx,Vy); VF = collision
-    DrawSprite(u8, u8, u8),
+    DrawSprite(VReg, VReg, u8),
     // Ex9E -> Skip next if Key(Vx) is pressed
-    SkipIfKey(u8),
+    SkipIfKey(VReg),
     // ExA1 -> Skip next if Key(Vx) is not pressed
-    SkipIfNotKey(u8),
+    SkipIfNotKey(VReg),
     // Fx07 -> Vx = DelayTimer
-    LoadDelay(u8),
+    LoadDelay(VReg),
     // Fx0A -> Wait for a key to be pressed, and then Vx = Key
-    WaitForKey(u8),
+    WaitForKey(VReg),
     // Fx15 -> DelayTimer = Vx
-    SetDelay(u8),
+    SetDelay(VReg),
     // Fx18 -> SoundTimer = Vx
-    SetSound(u8),
+    SetSound(VReg),
     //  Fx1E -> I += Vx
-    AddToIndex(u8),
+    AddToIndex(VReg),
     // Fx29 -> I = [Digit(Vx)]
-    LoadDigit(u8),
+    LoadDigit(VReg),
     // Fx33 -> Stores BCD representation of Vx in I, I+1, I+2
-    LoadBCD(u8),
+    LoadBCD(VReg),
     // Fx55 -> [I+0..I+x] = [V0..Vx]
-    SaveMem(u8),
+    SaveMem(VReg),
     // Fx65 -> [V0..Vx] = [I+0..I+x]
-    LoadMem(u8),
+    LoadMem(VReg),
 }
 
 impl TryFrom<u16> for Instruction {
@@ -85,8 +139,9 @@ impl TryFrom<u16> for Instruction {
             (value & 0x000F) as u8,
         );
 
-        let nnn = (value & 0x0FFF) as u16;
+        let nnn = value & 0x0FFF;
         let kk = (value & 0x00FF) as u8;
+        let (x, y) = (VReg::new(nibbles.1), VReg::new(nibbles.2));
 
         match nibbles {
             (0x0, 0x0, 0xe, 0x0) => Ok(Self::ClearScreen),
@@ -94,51 +149,153 @@ impl TryFrom<u16> for Instruction {
             (0x0, _, _, _) => Ok(Self::NoOp),
             (0x1, _, _, _) => Ok(Self::Jump(nnn)),
             (0x2, _, _, _) => Ok(Self::Call(nnn)),
-            (0x3, x, _, _) => Ok(Self::SkipVxEqual(x, kk)),
-            (0x4, x, _, _) => Ok(Self::SkipVxNotEqual(x, kk)),
-            (0x5, x, y, 0) => Ok(Self::SkipEqual(x, y)),
-            (0x6, x, _, _) => Ok(Self::LoadVx(x, kk)),
-            (0x7, x, _, _) => Ok(Self::AddVx(x, kk)),
-            (0x8, x, y, 0x0) => Ok(Self::Set(x, y)),
-            (0x8, x, y, 0x1) => Ok(Self::Or(x, y)),
-            (0x8, x, y, 0x2) => Ok(Self::And(x, y)),
-            (0x8, x, y, 0x3) => Ok(Self::Xor(x, y)),
-            (0x8, x, y, 0x4) => Ok(Self::Add(x, y)),
-            (0x8, x, y, 0x5) => Ok(Self::Sub(x, y)),
-            (0x8, x, _, 0x6) => Ok(Self::ShiftRightVx(x)),
-            (0x8, x, y, 0x7) => Ok(Self::SubN(x, y)),
-            (0x8, x, _, 0xE) => Ok(Self::ShiftLeftVx(x)),
-            (0x9, x, y, 0) => Ok(Self::SkipNotEqual(x, y)),
+            (0x3, _, _, _) => Ok(Self::SkipVxEqual(x, kk)),
+            (0x4, _, _, _) => Ok(Self::SkipVxNotEqual(x, kk)),
+            (0x5, _, _, 0) => Ok(Self::SkipEqual(x, y)),
+            (0x6, _, _, _) => Ok(Self::LoadVx(x, kk)),
+            (0x7, _, _, _) => Ok(Self::AddVx(x, kk)),
+            (0x8, _, _, 0x0) => Ok(Self::Set(x, y)),
+            (0x8, _, _, 0x1) => Ok(Self::Or(x, y)),
+            (0x8, _, _, 0x2) => Ok(Self::And(x, y)),
+            (0x8, _, _, 0x3) => Ok(Self::Xor(x, y)),
+            (0x8, _, _, 0x4) => Ok(Self::Add(x, y)),
+            (0x8, _, _, 0x5) => Ok(Self::Sub(x, y)),
+            (0x8, _, _, 0x6) => Ok(Self::ShiftRightVx(x, y)),
+            (0x8, _, _, 0x7) => Ok(Self::SubN(x, y)),
+            (0x8, _, _, 0xE) => Ok(Self::ShiftLeftVx(x, y)),
+            (0x9, _, _, 0) => Ok(Self::SkipNotEqual(x, y)),
             (0xA, _, _, _) => Ok(Self::LoadI(nnn)),
-            (0xB, x, _, _) => Ok(Self::JumpOffset(x, nnn)),
-            (0xC, x, _, _) => Ok(Self::Rand(x, kk)),
-            (0xD, x, y, n) => Ok(Self::DrawSprite(x, y, n)),
-            (0xE, x, 0x9, 0xE) => Ok(Self::SkipIfKey(x)),
-            (0xE, x, 0xA, 0x1) => Ok(Self::SkipIfNotKey(x)),
-            (0xF, x, 0x0, 0x7) => Ok(Self::LoadDelay(x)),
-            (0xF, x, 0x0, 0xA) => Ok(Self::WaitForKey(x)),
-            (0xF, x, 0x1, 0x5) => Ok(Self::SetDelay(x)),
-            (0xF, x, 0x1, 0x8) => Ok(Self::SetSound(x)),
-            (0xF, x, 0x1, 0xE) => Ok(Self::AddToIndex(x)),
-            (0xF, x, 0x2, 0x9) => Ok(Self::LoadDigit(x)),
-            (0xF, x, 0x3, 0x3) => Ok(Self::LoadBCD(x)),
-            (0xF, x, 0x5, 0x5) => Ok(Self::SaveMem(x)),
-            (0xF, x, 0x6, 0x5) => Ok(Self::LoadMem(x)),
+            (0xB, _, _, _) => Ok(Self::JumpOffset(x, nnn)),
+            (0xC, _, _, _) => Ok(Self::Rand(x, kk)),
+            (0xD, _, _, n) => Ok(Self::DrawSprite(x, y, n)),
+            (0xE, _, 0x9, 0xE) => Ok(Self::SkipIfKey(x)),
+            (0xE, _, 0xA, 0x1) => Ok(Self::SkipIfNotKey(x)),
+            (0xF, _, 0x0, 0x7) => Ok(Self::LoadDelay(x)),
+            (0xF, _, 0x0, 0xA) => Ok(Self::WaitForKey(x)),
+            (0xF, _, 0x1, 0x5) => Ok(Self::SetDelay(x)),
+            (0xF, _, 0x1, 0x8) => Ok(Self::SetSound(x)),
+            (0xF, _, 0x1, 0xE) => Ok(Self::AddToIndex(x)),
+            (0xF, _, 0x2, 0x9) => Ok(Self::LoadDigit(x)),
+            (0xF, _, 0x3, 0x3) => Ok(Self::LoadBCD(x)),
+            (0xF, _, 0x5, 0x5) => Ok(Self::SaveMem(x)),
+            (0xF, _, 0x6, 0x5) => Ok(Self::LoadMem(x)),
             _ => Err(CPUError::InvalidOpcode(value)),
         }
     }
 }
 
+impl Instruction {
+    /// Disassembles an instruction as the CPU runs it with `quirks`, where
+    /// `Bnnn` adds Vx instead of V0 with the jump quirk
+    pub fn disassemble(&self, quirks: &Quirks) -> String {
+        match self {
+            Self::JumpOffset(x, addr) if quirks.jump => format!("JP {}, #{:03X}", x, addr),
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Disassembles an instruction with the mnemonics of Cowgod's reference,
+/// and hex numbers starting with '#'
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoOp => write!(f, "SYS"),
+            Self::ClearScreen => write!(f, "CLS"),
+            Self::Return => write!(f, "RET"),
+            Self::Jump(addr) => write!(f, "JP #{:03X}", addr),
+            Self::Call(addr) => write!(f, "CALL #{:03X}", addr),
+            Self::SkipVxEqual(x, value) => write!(f, "SE {}, #{:02X}", x, value),
+            Self::SkipVxNotEqual(x, value) => write!(f, "SNE {}, #{:02X}", x, value),
+            Self::SkipEqual(x, y) => write!(f, "SE {}, {}", x, y),
+            Self::LoadVx(x, value) => write!(f, "LD {}, #{:02X}", x, value),
+            Self::AddVx(x, value) => write!(f, "ADD {}, #{:02X}", x, value),
+            Self::Set(x, y) => write!(f, "LD {}, {}", x, y),
+            Self::Or(x, y) => write!(f, "OR {}, {}", x, y),
+            Self::And(x, y) => write!(f, "AND {}, {}", x, y),
+            Self::Xor(x, y) => write!(f, "XOR {}, {}", x, y),
+            Self::Add(x, y) => write!(f, "ADD {}, {}", x, y),
+            Self::Sub(x, y) => write!(f, "SUB {}, {}", x, y),
+            Self::ShiftRightVx(x, y) => write!(f, "SHR {}, {}", x, y),
+            Self::SubN(x, y) => write!(f, "SUBN {}, {}", x, y),
+            Self::ShiftLeftVx(x, y) => write!(f, "SHL {}, {}", x, y),
+            Self::SkipNotEqual(x, y) => write!(f, "SNE {}, {}", x, y),
+            Self::LoadI(addr) => write!(f, "LD I, #{:03X}", addr),
+            // the COSMAC VIP form, see `disassemble` for the jump quirk
+            Self::JumpOffset(_, addr) => write!(f, "JP V0, #{:03X}", addr),
+            Self::Rand(x, value) => write!(f, "RND {}, #{:02X}", x, value),
+            Self::DrawSprite(x, y, n) => write!(f, "DRW {}, {}, {}", x, y, n),
+            Self::SkipIfKey(x) => write!(f, "SKP {}", x),
+            Self::SkipIfNotKey(x) => write!(f, "SKNP {}", x),
+            Self::LoadDelay(x) => write!(f, "LD {}, DT", x),
+            Self::WaitForKey(x) => write!(f, "LD {}, K", x),
+            Self::SetDelay(x) => write!(f, "LD DT, {}", x),
+            Self::SetSound(x) => write!(f, "LD ST, {}", x),
+            Self::AddToIndex(x) => write!(f, "ADD I, {}", x),
+            Self::LoadDigit(x) => write!(f, "LD F, {}", x),
+            Self::LoadBCD(x) => write!(f, "LD B, {}", x),
+            Self::SaveMem(x) => write!(f, "LD [I], {}", x),
+            Self::LoadMem(x) => write!(f, "LD {}, [I]", x),
+        }
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::unnecessary_cast)]
 mod tests {
     use super::*;
 
+    fn v(x: u8) -> VReg {
+        VReg::new(x)
+    }
+
     #[test]
     fn test_try_from_invalid_opcode() {
         let res = Instruction::try_from(0xFFFF as u16);
         assert_eq!(res.unwrap_err(), CPUError::InvalidOpcode(0xFFFF));
     }
 
+    #[test]
+    fn test_vreg_up_to() {
+        let registers: Vec<usize> = v(0x2).up_to().map(VReg::index).collect();
+        assert_eq!(registers, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_display() {
+        let disassembly = |opcode: u16| Instruction::try_from(opcode).unwrap().to_string();
+
+        assert_eq!(disassembly(0x00E0), "CLS");
+        assert_eq!(disassembly(0x1208), "JP #208");
+        assert_eq!(disassembly(0x3A0F), "SE VA, #0F");
+        assert_eq!(disassembly(0x8AB4), "ADD VA, VB");
+        assert_eq!(disassembly(0xB123), "JP V0, #123");
+        assert_eq!(disassembly(0xD125), "DRW V1, V2, 5");
+        assert_eq!(disassembly(0xF065), "LD V0, [I]");
+    }
+
+    #[test]
+    fn test_disassemble_with_quirks() {
+        let instruction = Instruction::try_from(0xB123).unwrap();
+        let vip = Quirks {
+            jump: false,
+            ..Quirks::default()
+        };
+
+        assert_eq!(instruction.disassemble(&Quirks::default()), "JP V1, #123");
+        assert_eq!(instruction.disassemble(&vip), "JP V0, #123");
+        assert_eq!(
+            Instruction::ClearScreen.disassemble(&Quirks::default()),
+            "CLS"
+        );
+    }
+
+    #[test]
+    fn test_key_try_from() {
+        assert_eq!(Key::try_from(0xF).map(Key::index), Ok(0xF));
+        assert_eq!(Key::try_from(0x10), Err(CPUError::InvalidKey(0x10)));
+    }
+
     #[test]
     fn test_try_from_valid_opcodes() {
         assert_eq!(Instruction::try_from(0x0123), Ok(Instruction::NoOp));
@@ -148,117 +305,120 @@ mod tests {
         assert_eq!(Instruction::try_from(0x2123), Ok(Instruction::Call(0x123)));
         assert_eq!(
             Instruction::try_from(0x3A11),
-            Ok(Instruction::SkipVxEqual(0xA, 0x11))
+            Ok(Instruction::SkipVxEqual(v(0xA), 0x11))
         );
         assert_eq!(
             Instruction::try_from(0x4A11),
-            Ok(Instruction::SkipVxNotEqual(0xA, 0x11))
+            Ok(Instruction::SkipVxNotEqual(v(0xA), 0x11))
         );
         assert_eq!(
             Instruction::try_from(0x5AB0),
-            Ok(Instruction::SkipEqual(0xA, 0xB))
+            Ok(Instruction::SkipEqual(v(0xA), v(0xB)))
         );
         assert_eq!(
             Instruction::try_from(0x6122),
-            Ok(Instruction::LoadVx(0x1, 0x22))
+            Ok(Instruction::LoadVx(v(0x1), 0x22))
         );
         assert_eq!(
             Instruction::try_from(0x73FF),
-            Ok(Instruction::AddVx(0x3, 0xFF))
+            Ok(Instruction::AddVx(v(0x3), 0xFF))
         );
         assert_eq!(
             Instruction::try_from(0x8120),
-            Ok(Instruction::Set(0x1, 0x2))
+            Ok(Instruction::Set(v(0x1), v(0x2)))
+        );
+        assert_eq!(
+            Instruction::try_from(0x8AB1),
+            Ok(Instruction::Or(v(0xA), v(0xB)))
         );
-        assert_eq!(Instruction::try_from(0x8AB1), Ok(Instruction::Or(0xA, 0xB)));
         assert_eq!(
             Instruction::try_from(0x8AB2),
-            Ok(Instruction::And(0xA, 0xB))
+            Ok(Instruction::And(v(0xA), v(0xB)))
         );
         assert_eq!(
             Instruction::try_from(0x8AB3),
-            Ok(Instruction::Xor(0xA, 0xB))
+            Ok(Instruction::Xor(v(0xA), v(0xB)))
         );
         assert_eq!(
             Instruction::try_from(0x8AB4),
-            Ok(Instruction::Add(0xA, 0xB))
+            Ok(Instruction::Add(v(0xA), v(0xB)))
         );
         assert_eq!(
             Instruction::try_from(0x8AB5),
-            Ok(Instruction::Sub(0xA, 0xB))
+            Ok(Instruction::Sub(v(0xA), v(0xB)))
         );
         assert_eq!(
             Instruction::try_from(0x8AB6),
-            Ok(Instruction::ShiftRightVx(0xA))
+            Ok(Instruction::ShiftRightVx(v(0xA), v(0xB)))
         );
         assert_eq!(
             Instruction::try_from(0x8AB7),
-            Ok(Instruction::SubN(0xA, 0xB))
+            Ok(Instruction::SubN(v(0xA), v(0xB)))
         );
         assert_eq!(
             Instruction::try_from(0x8ABE),
-            Ok(Instruction::ShiftLeftVx(0xA))
+            Ok(Instruction::ShiftLeftVx(v(0xA), v(0xB)))
         );
         assert_eq!(
             Instruction::try_from(0x9AB0),
-            Ok(Instruction::SkipNotEqual(0xA, 0xB))
+            Ok(Instruction::SkipNotEqual(v(0xA), v(0xB)))
         );
         assert_eq!(Instruction::try_from(0xABCD), Ok(Instruction::LoadI(0xBCD)));
         assert_eq!(
             Instruction::try_from(0xB123),
-            Ok(Instruction::JumpOffset(0x01, 0x123))
+            Ok(Instruction::JumpOffset(v(0x01), 0x123))
         );
         assert_eq!(
             Instruction::try_from(0xC0AB),
-            Ok(Instruction::Rand(0x0, 0xAB)),
+            Ok(Instruction::Rand(v(0x0), 0xAB)),
         );
         assert_eq!(
             Instruction::try_from(0xD12A),
-            Ok(Instruction::DrawSprite(0x1, 0x2, 0xA))
+            Ok(Instruction::DrawSprite(v(0x1), v(0x2), 0xA))
         );
         assert_eq!(
             Instruction::try_from(0xE79E),
-            Ok(Instruction::SkipIfKey(0x07))
+            Ok(Instruction::SkipIfKey(v(0x07)))
         );
         assert_eq!(
             Instruction::try_from(0xE7A1),
-            Ok(Instruction::SkipIfNotKey(0x07))
+            Ok(Instruction::SkipIfNotKey(v(0x07)))
         );
         assert_eq!(
             Instruction::try_from(0xF007),
-            Ok(Instruction::LoadDelay(0x00))
+            Ok(Instruction::LoadDelay(v(0x00)))
         );
         assert_eq!(
             Instruction::try_from(0xF00A),
-            Ok(Instruction::WaitForKey(0x00))
+            Ok(Instruction::WaitForKey(v(0x00)))
         );
         assert_eq!(
             Instruction::try_from(0xF015),
-            Ok(Instruction::SetDelay(0x00))
+            Ok(Instruction::SetDelay(v(0x00)))
         );
         assert_eq!(
             Instruction::try_from(0xF018),
-            Ok(Instruction::SetSound(0x00))
+            Ok(Instruction::SetSound(v(0x00)))
         );
         assert_eq!(
             Instruction::try_from(0xF01E),
-            Ok(Instruction::AddToIndex(0x00))
+            Ok(Instruction::AddToIndex(v(0x00)))
         );
         assert_eq!(
             Instruction::try_from(0xF029),
-            Ok(Instruction::LoadDigit(0x00))
+            Ok(Instruction::LoadDigit(v(0x00)))
         );
         assert_eq!(
             Instruction::try_from(0xFA33),
-            Ok(Instruction::LoadBCD(0x0A))
+            Ok(Instruction::LoadBCD(v(0x0A)))
         );
         assert_eq!(
             Instruction::try_from(0xFA55),
-            Ok(Instruction::SaveMem(0x0A))
+            Ok(Instruction::SaveMem(v(0x0A)))
         );
         assert_eq!(
             Instruction::try_from(0xFA65),
-            Ok(Instruction::LoadMem(0x0A))
+            Ok(Instruction::LoadMem(v(0x0A)))
         );
     }
 }
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::path::Path;
+
+use sdl2::controller::Button;
+use sdl2::keyboard::Scancode;
+use serde::Deserialize;
+
+use crate::error::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+const KEYPAD_SIZE: u8 = 16;
+
+// Built-in keymaps, as (name, TOML source)
+const PRESETS: [(&str, &str); 5] = [
+    ("qwerty", include_str!("../keymaps/qwerty.toml")),
+    ("azerty", include_str!("../keymaps/azerty.toml")),
+    ("qwertz", include_str!("../keymaps/qwertz.toml")),
+    ("dvorak", include_str!("../keymaps/dvorak.toml")),
+    ("numpad", include_str!("../keymaps/numpad.toml")),
+];
+
+// Keys and buttons bound to game actions, as named in the CHIP-8 database,
+// and the CHIP-8 key of each action when a game doesn't set it
+const ACTIONS: [(&str, Scancode, Button, u8); 6] = [
+    ("up", Scancode::Up, Button::DPadUp, 0x5),
+    ("down", Scancode::Down, Button::DPadDown, 0x8),
+    ("left", Scancode::Left, Button::DPadLeft, 0x7),
+    ("right", Scancode::Right, Button::DPadRight, 0x9),
+    ("a", Scancode::Space, Button::A, 0x6),
+    ("b", Scancode::LShift, Button::B, 0x4),
+];
+
+#[derive(Deserialize)]
+struct KeymapFile {
+    keys: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    buttons: HashMap<String, Vec<String>>,
+}
+
+/// Maps SDL scancodes and game controller buttons to CHIP-8 keys (0x0 to
+/// 0xF). A CHIP-8 key can be bound to any number of scancodes and buttons.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    keys: HashMap<Scancode, u8>,
+    buttons: HashMap<Button, u8>,
+}
+
+impl Keymap {
+    /// Loads a built-in preset by name, or a TOML keymap file otherwise.
+    pub fn load(name_or_path: &str) -> Result<Self> {
+        match Self::preset(name_or_path) {
+            Some(keymap) => keymap,
+            None => Self::from_file(name_or_path),
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Result<Self>> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, source)| Self::from_toml(source))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let source = fs::read_to_string(path)?;
+        Self::from_toml(&source)
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self> {
+        let file: KeymapFile = toml::from_str(source)?;
+
+        Ok(Self {
+            keys: parse_bindings(file.keys, Scancode::from_name)?,
+            buttons: parse_bindings(file.buttons, Button::from_string)?,
+        })
+    }
+
+    /// Binds the arrow keys, space, left shift, the D-pad and the A/B buttons
+    /// to the CHIP-8 keys a game uses for each action (i.e. "up" or "a").
+    /// Keys and buttons the keymap already binds are left as they are.
+    pub fn with_actions(mut self, actions: &HashMap<String, u8>) -> Self {
+        for (action, code, button, default_key) in ACTIONS {
+            let key = actions.get(action).copied().unwrap_or(default_key);
+            self.keys.entry(code).or_insert(key);
+            self.buttons.entry(button).or_insert(key);
+        }
+
+        self
+    }
+
+    pub fn get(&self, code: &Scancode) -> Option<u8> {
+        self.keys.get(code).copied()
+    }
+
+    pub fn get_button(&self, button: &Button) -> Option<u8> {
+        self.buttons.get(button).copied()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset("qwerty")
+            .expect("Missing default keymap")
+            .expect("Invalid default keymap")
+            .with_actions(&HashMap::new())
+    }
+}
+
+fn parse_bindings<T: Hash + Eq>(
+    bindings: HashMap<String, Vec<String>>,
+    from_name: impl Fn(&str) -> Option<T>,
+) -> Result<HashMap<T, u8>> {
+    let mut parsed = HashMap::new();
+
+    for (key, names) in bindings {
+        let key = parse_key(&key)?;
+
+        for name in names {
+            let binding = from_name(&name).ok_or_else(|| {
+                Error::ConfigError(format!("Unknown key or button name in keymap: {}", name))
+            })?;
+
+            if parsed
+                .insert(binding, key)
+                .is_some_and(|other| other != key)
+            {
+                return Err(Error::ConfigError(format!(
+                    "{} is bound to more than one CHIP-8 key",
+                    name
+                )));
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn parse_key(key: &str) -> Result<u8> {
+    let digits = key.strip_prefix("0x").unwrap_or(key);
+    match u8::from_str_radix(digits, 16) {
+        Ok(x) if x < KEYPAD_SIZE => Ok(x),
+        _ => Err(Error::ConfigError(format!(
+            "Invalid CHIP-8 key in keymap: {}",
+            key
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let keymap = Keymap::from_toml(
+            r#"
+            [keys]
+            0 = ["X"]
+            0xA = ["Z", "Keypad 0"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(keymap.get(&Scancode::X), Some(0x0));
+        assert_eq!(keymap.get(&Scancode::Z), Some(0xA));
+        assert_eq!(keymap.get(&Scancode::Kp0), Some(0xA));
+        assert_eq!(keymap.get(&Scancode::C), None);
+        assert_eq!(keymap.get_button(&Button::A), None);
+    }
+
+    #[test]
+    fn test_from_toml_with_buttons() {
+        let keymap = Keymap::from_toml(
+            r#"
+            [keys]
+            5 = ["W"]
+
+            [buttons]
+            5 = ["dpup"]
+            6 = ["a", "b"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(keymap.get(&Scancode::W), Some(0x5));
+        assert_eq!(keymap.get_button(&Button::DPadUp), Some(0x5));
+        assert_eq!(keymap.get_button(&Button::A), Some(0x6));
+        assert_eq!(keymap.get_button(&Button::B), Some(0x6));
+    }
+
+    #[test]
+    fn test_from_toml_returns_err_on_unknown_button_name() {
+        let res = Keymap::from_toml("[keys]\n\n[buttons]\n0 = [\"NotAButton\"]");
+        assert!(matches!(res, Err(Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_from_toml_returns_err_on_invalid_key() {
+        let res = Keymap::from_toml("[keys]\n10 = [\"X\"]");
+        assert!(matches!(res, Err(Error::ConfigError(_))));
+
+        let res = Keymap::from_toml("[keys]\n0x0x5 = [\"X\"]");
+        assert!(matches!(res, Err(Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_from_toml_returns_err_on_unknown_key_name() {
+        let res = Keymap::from_toml("[keys]\n0 = [\"NotAKey\"]");
+        assert!(matches!(res, Err(Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_from_toml_returns_err_on_key_bound_twice() {
+        let res = Keymap::from_toml("[keys]\n0 = [\"X\"]\n1 = [\"X\"]");
+        assert!(matches!(res, Err(Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_load_presets() {
+        for (name, _) in PRESETS {
+            assert!(Keymap::load(name).is_ok(), "{} is not a preset", name);
+        }
+        assert!(Keymap::load("AZERTY").is_ok());
+    }
+
+    #[test]
+    fn test_presets_bind_every_key() {
+        for (name, _) in PRESETS {
+            let keymap = Keymap::preset(name).unwrap().unwrap();
+            for key in 0..KEYPAD_SIZE {
+                assert!(
+                    keymap.keys.values().any(|x| *x == key),
+                    "{} does not bind key {:#X}",
+                    name,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+
+        assert_eq!(keymap.get(&Scancode::Num1), Some(0x1));
+        assert_eq!(keymap.get(&Scancode::W), Some(0x5));
+        assert_eq!(keymap.get(&Scancode::Up), Some(0x5));
+        assert_eq!(keymap.get(&Scancode::V), Some(0xF));
+    }
+
+    #[test]
+    fn test_with_actions() {
+        let actions = HashMap::from([("left".to_string(), 0x4), ("a".to_string(), 0x5)]);
+
+        let keymap = Keymap::preset("qwerty")
+            .unwrap()
+            .unwrap()
+            .with_actions(&actions);
+
+        assert_eq!(keymap.get(&Scancode::Left), Some(0x4));
+        assert_eq!(keymap.get_button(&Button::DPadLeft), Some(0x4));
+        assert_eq!(keymap.get(&Scancode::Space), Some(0x5));
+        assert_eq!(keymap.get_button(&Button::A), Some(0x5));
+        // actions the game doesn't set get their default key
+        assert_eq!(keymap.get(&Scancode::Right), Some(0x9));
+        assert_eq!(keymap.get_button(&Button::B), Some(0x4));
+        // keys without an action keep their binding
+        assert_eq!(keymap.get(&Scancode::A), Some(0x7));
+    }
+
+    #[test]
+    fn test_with_actions_keeps_bound_keys() {
+        let actions = HashMap::from([("left".to_string(), 0x4)]);
+
+        let keymap = Keymap::from_toml("[keys]\n1 = [\"Left\"]\n\n[buttons]\n2 = [\"dpleft\"]")
+            .unwrap()
+            .with_actions(&actions);
+
+        assert_eq!(keymap.get(&Scancode::Left), Some(0x1));
+        assert_eq!(keymap.get_button(&Button::DPadLeft), Some(0x2));
+    }
+
+    #[test]
+    fn test_load_returns_err_on_missing_file() {
+        let res = Keymap::load("not-a-preset.toml");
+        assert!(matches!(res, Err(Error::IOError(_))));
+    }
+}
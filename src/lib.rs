@@ -1,16 +1,150 @@
 mod audio;
+mod clock;
+mod config;
 mod cpu;
+mod database;
+mod emulation;
 mod error;
+mod filters;
+mod font;
+mod framebuffer;
+mod gamepad;
+mod input;
 mod instruction;
+mod keymap;
+mod osd;
+mod palette;
+mod panel;
+mod phosphor;
+mod quirks;
 mod screen;
+mod screenshot;
+mod script;
+pub mod settings;
 mod sprites;
+mod video;
+mod viewer;
 pub mod vm;
+mod wav;
 
-use std::path::PathBuf;
+pub use audio::Waveform;
+pub use filters::Filter;
+pub use palette::PaletteSetting;
+pub use phosphor::Persistence;
+pub use script::KeyScript;
 
-pub fn run(filename: PathBuf) -> vm::Result<()> {
-    let mut rng = rand::thread_rng();
-    let mut vm = vm::VM::new(&mut rng);
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Runs a ROM with the settings given in `overrides`, optionally saving them
+/// for the next runs of the same ROM. Random numbers are the same on every run
+/// given a `seed`. The buzzer is recorded to `record_audio` and the screen to
+/// `record` (a GIF file or a directory of PNG files) if given.
+pub fn run(
+    filename: PathBuf,
+    overrides: settings::Settings,
+    save: Option<settings::Location>,
+    seed: Option<u64>,
+    record_audio: Option<PathBuf>,
+    record: Option<PathBuf>,
+) -> vm::Result<()> {
+    let defaults = settings::Settings::load_global()?;
+
+    let mut vm = vm::VM::new(rng(seed), overrides, defaults);
     vm.load_rom(filename)?;
+    if let Some(path) = record_audio {
+        vm.record_audio(path);
+    }
+    if let Some(path) = record {
+        vm.record_video(path, screen::DEFAULT_SCALE);
+    }
+
+    if let Some(location) = save {
+        let path = vm.save_settings(location)?;
+        println!("Settings saved to {}", path.display());
+    }
+
     vm.run()
 }
+
+/// A run of a ROM without a window, for a number of frames, pressing the
+/// keys of `keys`. The buzzer is recorded to `record_audio` if given.
+pub struct HeadlessRun {
+    pub filename: PathBuf,
+    pub overrides: settings::Settings,
+    pub seed: Option<u64>,
+    pub keys: KeyScript,
+    pub frames: u32,
+    pub record_audio: Option<PathBuf>,
+}
+
+/// Runs a ROM without a window, then saves a screenshot to `output` (PNG, or
+/// PPM by extension)
+pub fn screenshot(run: HeadlessRun, output: &Path, scale: u32) -> vm::Result<()> {
+    // before running, to report an unsupported format right away
+    screenshot::Format::from_path(output)?;
+    let mut vm = headless_vm(&run)?;
+    run_script(&mut vm, &run.keys, run.frames)?;
+
+    vm.screenshot(output, scale)?;
+    vm.finish_headless()
+}
+
+/// Runs a ROM without a window, while recording the screen to `output`: a GIF
+/// file, or a directory of PNG files
+pub fn record(run: HeadlessRun, output: &Path, scale: u32) -> vm::Result<()> {
+    let mut vm = headless_vm(&run)?;
+    vm.record_video(output.to_path_buf(), scale);
+    run_script(&mut vm, &run.keys, run.frames)?;
+
+    vm.finish_headless()
+}
+
+/// Prints the CHIP-8 database entry matching a ROM
+pub fn info(filename: PathBuf) -> vm::Result<()> {
+    let rom = fs::read(filename)?;
+
+    match database::lookup(&rom) {
+        Some(info) => println!("{}", info),
+        None => println!(
+            "No match in the CHIP-8 database (SHA-1: {})",
+            database::sha1(&rom)
+        ),
+    }
+
+    Ok(())
+}
+
+fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+fn headless_vm(run: &HeadlessRun) -> vm::Result<vm::VM> {
+    let defaults = settings::Settings::load_global()?;
+
+    let mut vm = vm::VM::new(rng(run.seed), run.overrides.clone(), defaults);
+    vm.load_rom(run.filename.clone())?;
+    if let Some(path) = &run.record_audio {
+        vm.record_audio(path.clone());
+    }
+
+    Ok(vm)
+}
+
+// runs a frame (timer tick) at a time, with the keys of the script held down
+fn run_script(vm: &mut vm::VM, keys: &KeyScript, frames: u32) -> vm::Result<()> {
+    for frame in 0..frames {
+        for (key, is_pressed) in keys.keys_at(frame).into_iter().enumerate() {
+            vm.set_key(key as u8, is_pressed)?;
+        }
+        vm.run_headless(1)?;
+    }
+
+    Ok(())
+}
@@ -1,20 +1,296 @@
-use clap::{Args, Parser};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use chip8_rs::settings::{Location, Settings};
+use chip8_rs::{Filter, HeadlessRun, KeyScript, PaletteSetting, Persistence, Waveform};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about=None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[command(flatten)]
-    run: CliArgs,
+    run: Option<CliArgs>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a ROM (default)
+    Run(CliArgs),
+    /// Print the settings found for a ROM in the CHIP-8 database
+    Info { file: std::path::PathBuf },
+    /// Run a ROM without a window, then save a screenshot
+    Screenshot {
+        #[command(flatten)]
+        headless: HeadlessArgs,
+        /// PNG or PPM file to save the screenshot to
+        #[arg(short, long)]
+        output: std::path::PathBuf,
+    },
+    /// Run a ROM without a window, recording the screen
+    Record {
+        #[command(flatten)]
+        headless: HeadlessArgs,
+        /// GIF file, or directory of PNG files, to record to
+        #[arg(short, long)]
+        output: std::path::PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct HeadlessArgs {
+    file: std::path::PathBuf,
+    /// Frames (60 Hz timer ticks) to run
+    #[arg(long, default_value_t = 60)]
+    frames: u32,
+    /// Keys to press, as KEY@FRAME or KEY@START..END (i.e. "5@10..20,A@60")
+    #[arg(short, long, default_value = "", conflicts_with = "movie")]
+    keys: KeyScript,
+    /// File with the keys to press, in the format of --keys
+    #[arg(short, long)]
+    movie: Option<std::path::PathBuf>,
+    /// Pixels per CHIP-8 pixel
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    scale: u32,
+    /// Palette name, or a list of colors starting with the background [default: classic]
+    #[arg(short, long, value_parser = parse_palette)]
+    palette: Option<PaletteSetting>,
+    /// Seed for the random numbers of Cxkk
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Record the buzzer to a WAV file
+    #[arg(long, value_name = "FILE")]
+    record_audio: Option<std::path::PathBuf>,
 }
 
 #[derive(Args)]
 struct CliArgs {
     file: std::path::PathBuf,
+    /// Keymap preset (qwerty, azerty, qwertz, dvorak, numpad) or path to a TOML keymap file [default: qwerty]
+    #[arg(short, long)]
+    keymap: Option<String>,
+    /// Wait for keys to be released in Fx0A, like the COSMAC VIP
+    #[arg(long, overrides_with = "no_wait_key_release")]
+    wait_key_release: bool,
+    /// Wait for keys to be pressed in Fx0A, overriding saved settings
+    #[arg(long, overrides_with = "wait_key_release")]
+    no_wait_key_release: bool,
+    /// Rumble game controllers while the buzzer sounds
+    #[arg(long, overrides_with = "no_rumble")]
+    rumble: bool,
+    /// Don't rumble game controllers, overriding saved settings
+    #[arg(long, overrides_with = "rumble")]
+    no_rumble: bool,
+    /// Instructions per timer tick (60 Hz), as in the CHIP-8 database
+    #[arg(short, long, conflicts_with = "ips")]
+    tickrate: Option<u32>,
+    /// Instructions per second
+    #[arg(long)]
+    ips: Option<u32>,
+    /// Run the CPU on its own thread, so slow frames don't hold it back
+    #[arg(long, overrides_with = "no_threaded")]
+    threaded: bool,
+    /// Run the CPU on the same thread as the window, overriding saved settings
+    #[arg(long, overrides_with = "threaded")]
+    no_threaded: bool,
+    /// How many times faster to run while Tab is held [default: 4]
+    #[arg(long, value_name = "MULTIPLIER")]
+    fast_forward: Option<u32>,
+    /// Show the frames and instructions per second over the screen
+    #[arg(long, overrides_with = "no_show_fps")]
+    show_fps: bool,
+    /// Don't show the frames and instructions per second, overriding saved settings
+    #[arg(long, overrides_with = "show_fps")]
+    no_show_fps: bool,
+    /// Frames per second
+    #[arg(long)]
+    fps: Option<f64>,
+    /// Window size, in pixels per CHIP-8 pixel [default: 10, or the last window size]
+    #[arg(short, long)]
+    scale: Option<u32>,
+    /// Start in fullscreen mode (toggle with F11 or Alt+Enter)
+    #[arg(short, long, overrides_with = "no_fullscreen")]
+    fullscreen: bool,
+    /// Start in a window, overriding saved settings
+    #[arg(long, overrides_with = "fullscreen")]
+    no_fullscreen: bool,
+    /// Scale the screen by whole numbers only, so all pixels have the same size
+    #[arg(long, overrides_with = "no_integer_scale")]
+    integer_scale: bool,
+    /// Scale the screen to fit the window, overriding saved settings
+    #[arg(long, overrides_with = "integer_scale")]
+    no_integer_scale: bool,
+    /// Palette (classic, amber, green, lcd, octo, or one from the config file), or a list of colors starting with the background (i.e. "#000000,#ffffff") [default: classic]
+    #[arg(short, long, value_parser = parse_palette)]
+    palette: Option<PaletteSetting>,
+    /// Keep pixels lit after they turn off, to reduce flicker: off, blend (show the last two frames) or a number of frames to fade out over [default: off]
+    #[arg(long)]
+    persistence: Option<Persistence>,
+    /// Upscaling filter: none, scale2x (or epx), scale3x, hqx, scanlines, crt [default: none]
+    #[arg(long)]
+    filter: Option<Filter>,
+    /// Buzzer volume, from 0.0 [default: 1.0]
+    #[arg(long)]
+    volume: Option<f32>,
+    /// Buzzer frequency, in Hz [default: 349.23]
+    #[arg(long)]
+    tone: Option<f32>,
+    /// Buzzer waveform (square, sine, triangle) [default: sine]
+    #[arg(long)]
+    waveform: Option<Waveform>,
+    /// Seed for the random numbers of Cxkk, to make runs reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Record the buzzer to a WAV file (start and stop recording with F7)
+    #[arg(long, value_name = "FILE")]
+    record_audio: Option<std::path::PathBuf>,
+    /// Record the screen to a GIF file, or to a directory of PNG files (start and stop recording with F8)
+    #[arg(long, value_name = "FILE")]
+    record: Option<std::path::PathBuf>,
+    /// Enable or disable a quirk, as named in the CHIP-8 database (i.e. vblank=true)
+    #[arg(short, long, value_name = "QUIRK=BOOL", value_parser = parse_quirk)]
+    quirk: Vec<(String, bool)>,
+    /// Save these settings for the next runs of the ROM
+    #[arg(
+        long,
+        value_name = "WHERE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "config"
+    )]
+    save: Option<SaveTo>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SaveTo {
+    /// In the config directory, by the ROM's SHA-1 hash
+    Config,
+    /// In a TOML file next to the ROM
+    Sidecar,
+}
+
+impl CliArgs {
+    fn settings(&self) -> chip8_rs::vm::Result<Settings> {
+        let mut settings = Settings {
+            tickrate: self.tickrate,
+            ips: self.ips,
+            threaded: flag(self.threaded, self.no_threaded),
+            fast_forward: self.fast_forward,
+            show_fps: flag(self.show_fps, self.no_show_fps),
+            fps: self.fps,
+            scale: self.scale,
+            fullscreen: flag(self.fullscreen, self.no_fullscreen),
+            integer_scale: flag(self.integer_scale, self.no_integer_scale),
+            palette: self.palette.clone(),
+            persistence: self.persistence,
+            filter: self.filter,
+            volume: self.volume,
+            tone: self.tone,
+            waveform: self.waveform,
+            keymap: self.keymap.clone(),
+            wait_key_release: flag(self.wait_key_release, self.no_wait_key_release),
+            rumble: flag(self.rumble, self.no_rumble),
+            ..Settings::default()
+        };
+        for (name, value) in &self.quirk {
+            settings.quirks.set(name, *value)?;
+        }
+
+        Ok(settings)
+    }
+}
+
+impl HeadlessArgs {
+    fn run(self) -> chip8_rs::vm::Result<HeadlessRun> {
+        let keys = match &self.movie {
+            Some(path) => KeyScript::load(path)?,
+            None => self.keys,
+        };
+
+        Ok(HeadlessRun {
+            filename: self.file,
+            overrides: Settings {
+                palette: self.palette,
+                ..Settings::default()
+            },
+            seed: self.seed,
+            keys,
+            frames: self.frames,
+            record_audio: self.record_audio,
+        })
+    }
+}
+
+// a flag turned on with --name, or off with --no-name, if given at all
+fn flag(on: bool, off: bool) -> Option<bool> {
+    if on {
+        Some(true)
+    } else if off {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn parse_palette(arg: &str) -> Result<PaletteSetting, String> {
+    if arg.starts_with('#') {
+        let colors = arg.split(',').map(|color| color.trim().to_string());
+        Ok(PaletteSetting::Colors(colors.collect()))
+    } else {
+        Ok(PaletteSetting::Name(arg.to_string()))
+    }
+}
+
+fn parse_quirk(arg: &str) -> Result<(String, bool), String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| "expected QUIRK=BOOL".to_string())?;
+    let value = value
+        .parse()
+        .map_err(|_| format!("invalid value: {}", value))?;
+
+    Ok((name.to_string(), value))
 }
 
 fn main() {
     let cli = Cli::parse();
-    match chip8_rs::run(cli.run.file) {
+    let command = match (cli.command, cli.run) {
+        (Some(command), _) => command,
+        (None, Some(args)) => Command::Run(args),
+        (None, None) => unreachable!("clap requires either a command or a file"),
+    };
+
+    let res = match command {
+        Command::Run(args) => args.settings().and_then(|settings| {
+            let save = args.save.map(|save| match save {
+                SaveTo::Config => Location::Config,
+                SaveTo::Sidecar => Location::Sidecar,
+            });
+            chip8_rs::run(
+                args.file,
+                settings,
+                save,
+                args.seed,
+                args.record_audio,
+                args.record,
+            )
+        }),
+        Command::Info { file } => chip8_rs::info(file),
+        Command::Screenshot { headless, output } => {
+            let scale = headless.scale;
+            headless
+                .run()
+                .and_then(|run| chip8_rs::screenshot(run, &output, scale))
+        }
+        Command::Record { headless, output } => {
+            let scale = headless.scale;
+            headless
+                .run()
+                .and_then(|run| chip8_rs::record(run, &output, scale))
+        }
+    };
+
+    match res {
         Ok(()) => {}
         Err(e) => {
             eprintln!("{}", e);
@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+use crate::error::{to_sdl_err, Error};
+use crate::font::{self, GLYPH_HEIGHT};
+
+// how long messages stay on screen
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const TEXT_COLOR: Color = Color::RGB(255, 255, 255);
+// behind the text, so it can be read over any palette
+const BACKDROP_COLOR: Color = Color::RGBA(0, 0, 0, 160);
+// height of the CHIP-8 screen, in window pixels, per font pixel
+const PIXELS_PER_SCALE: u32 = 160;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The on-screen display, drawn over the CHIP-8 screen: a frame and
+/// instruction counter at the top, and at the bottom the last message for a
+/// while, or else a status that stays (i.e. "Paused").
+#[derive(Debug, Default)]
+pub struct Osd {
+    // the message shown, and since when
+    message: Option<(String, Instant)>,
+    status: Option<String>,
+    counter: Option<String>,
+}
+
+impl Osd {
+    pub fn show(&mut self, message: String, now: Instant) {
+        self.message = Some((message, now));
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    pub fn set_counter(&mut self, counter: Option<String>) {
+        self.counter = counter;
+    }
+
+    // the text shown at the bottom at `now`
+    fn bottom_line(&self, now: Instant) -> Option<&str> {
+        match &self.message {
+            Some((message, since)) if now.duration_since(*since) < MESSAGE_DURATION => {
+                Some(message)
+            }
+            _ => self.status.as_deref(),
+        }
+    }
+
+    /// Draws the display over the area of the window showing the screen
+    pub fn draw(&self, canvas: &mut Canvas<Window>, viewport: Rect, now: Instant) -> Result<()> {
+        let scale = text_scale(viewport);
+        let margin = (2 * scale) as i32;
+
+        if let Some(counter) = &self.counter {
+            let position = (viewport.x() + margin, viewport.y() + margin);
+            draw_line(canvas, counter, position, scale)?;
+        }
+        if let Some(line) = self.bottom_line(now) {
+            let y = viewport.bottom() - margin - (GLYPH_HEIGHT * scale) as i32;
+            draw_line(canvas, line, (viewport.x() + margin, y), scale)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How many times larger to draw text over the screen, so it keeps the same
+/// size relative to it
+pub fn text_scale(viewport: Rect) -> u32 {
+    (viewport.height() / PIXELS_PER_SCALE).max(1)
+}
+
+// draws a line of text on a translucent backdrop
+fn draw_line(
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    (x, y): (i32, i32),
+    scale: u32,
+) -> Result<()> {
+    let (width, height) = font::text_size(text, scale);
+    let padding = scale as i32;
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(BACKDROP_COLOR);
+    canvas
+        .fill_rect(Rect::new(
+            x - padding,
+            y - padding,
+            width + 2 * scale,
+            height + 2 * scale,
+        ))
+        .map_err(to_sdl_err)?;
+    canvas.set_blend_mode(BlendMode::None);
+
+    font::draw_text(canvas, text, (x, y), scale, TEXT_COLOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_show_for_a_while_over_the_status() {
+        let start = Instant::now();
+        let mut osd = Osd::default();
+        osd.set_status(Some("Paused".to_string()));
+
+        osd.show("Muted".to_string(), start);
+
+        assert_eq!(
+            osd.bottom_line(start + Duration::from_secs(1)),
+            Some("Muted")
+        );
+        assert_eq!(osd.bottom_line(start + MESSAGE_DURATION), Some("Paused"));
+        osd.set_status(None);
+        assert_eq!(osd.bottom_line(start + MESSAGE_DURATION), None);
+    }
+
+    #[test]
+    fn test_text_scale() {
+        assert_eq!(text_scale(Rect::new(0, 0, 640, 320)), 2);
+        assert_eq!(text_scale(Rect::new(0, 0, 64, 32)), 1);
+        assert_eq!(text_scale(Rect::new(0, 0, 1920, 960)), 6);
+    }
+}
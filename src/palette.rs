@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+
+use sdl2::pixels::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub const DEFAULT_PALETTE: &str = "classic";
+// name of the palette given as a list of colors instead of by name
+const CUSTOM_PALETTE: &str = "custom";
+
+// Built-in palettes, as (name, pixel colors, border color). Pixel colors are
+// indexed by the value of a pixel: the background, the foreground, and the
+// colors of the other planes for four-color modes.
+const BUILTIN: [(&str, [u32; 4], u32); 5] = [
+    (
+        "classic",
+        [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555],
+        0x000000,
+    ),
+    ("amber", [0x1A0F00, 0xFFB000, 0xB06000, 0xFFE0A0], 0x000000),
+    ("green", [0x001A08, 0x33FF66, 0x10A040, 0xB0FFC8], 0x000000),
+    ("lcd", [0xC7F0D8, 0x43523D, 0x8FA88A, 0x1F261C], 0x8FA88A),
+    // Octo's background, fill, fill 2 and blend colors
+    ("octo", [0x996600, 0xFFCC00, 0xFF6600, 0x662200], 0x000000),
+];
+
+/// A palette chosen by name, or given as a list of "#rrggbb" pixel colors
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PaletteSetting {
+    Name(String),
+    Colors(Vec<String>),
+}
+
+/// The colors of a user-defined palette, as "#rrggbb"
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct PaletteColors {
+    // background first, then foreground, then the colors of the other planes
+    pub pixels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Palette {
+    pub name: String,
+    // indexed by pixel value, with at least a background and a foreground
+    pixels: Vec<Color>,
+    pub border: Color,
+}
+
+impl Palette {
+    /// The built-in palettes, followed by the user-defined ones
+    pub fn all(user: &BTreeMap<String, PaletteColors>) -> Result<Vec<Self>> {
+        let builtin = BUILTIN.iter().map(|(name, pixels, border)| Self {
+            name: name.to_string(),
+            pixels: pixels.iter().copied().map(rgb).collect(),
+            border: rgb(*border),
+        });
+
+        let user = user
+            .iter()
+            .filter(|(name, _)| !BUILTIN.iter().any(|(builtin, _, _)| builtin == name))
+            .map(|(name, colors)| Self::from_colors(name, colors));
+
+        builtin.map(Ok).chain(user).collect()
+    }
+
+    /// Resolves a palette setting, where user-defined palettes can't replace
+    /// the built-in ones
+    pub fn find(
+        setting: Option<&PaletteSetting>,
+        user: &BTreeMap<String, PaletteColors>,
+    ) -> Result<Self> {
+        let name = match setting {
+            Some(PaletteSetting::Colors(pixels)) => {
+                return Self::from_colors(
+                    CUSTOM_PALETTE,
+                    &PaletteColors {
+                        pixels: pixels.clone(),
+                        border: None,
+                    },
+                )
+            }
+            Some(PaletteSetting::Name(name)) => name.as_str(),
+            None => DEFAULT_PALETTE,
+        };
+
+        Self::all(user)?
+            .into_iter()
+            .find(|palette| palette.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::ConfigError(format!("Unknown palette: {}", name)))
+    }
+
+    fn from_colors(name: &str, colors: &PaletteColors) -> Result<Self> {
+        if colors.pixels.len() < 2 {
+            return Err(Error::ConfigError(format!(
+                "Palette {} needs a background and a foreground color",
+                name
+            )));
+        }
+
+        let pixels = colors
+            .pixels
+            .iter()
+            .map(|hex| parse_color(hex))
+            .collect::<Result<Vec<Color>>>()?;
+        let border = match &colors.border {
+            Some(hex) => parse_color(hex)?,
+            None => pixels[0],
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            pixels,
+            border,
+        })
+    }
+
+    /// The setting that picks this palette: its name, or its colors when it
+    /// was given as a list of colors
+    pub fn setting(&self) -> PaletteSetting {
+        if self.name == CUSTOM_PALETTE {
+            let pixels = self
+                .pixels
+                .iter()
+                .map(|color| format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b));
+            PaletteSetting::Colors(pixels.collect())
+        } else {
+            PaletteSetting::Name(self.name.clone())
+        }
+    }
+
+    pub fn foreground(&self) -> Color {
+        self.pixels[1]
+    }
+
+    /// The color of a pixel value, where values missing from the palette use
+    /// the foreground color
+    pub fn pixel(&self, value: u8) -> Color {
+        self.pixels
+            .get(value as usize)
+            .copied()
+            .unwrap_or(self.foreground())
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::find(None, &BTreeMap::new()).expect("Missing default palette")
+    }
+}
+
+/// Parses a "#rrggbb" color
+pub fn parse_hex_color(hex: &str) -> Option<Color> {
+    let digits = hex.strip_prefix('#')?;
+    if digits.len() != 6 {
+        return None;
+    }
+
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    Some(rgb(value))
+}
+
+fn parse_color(hex: &str) -> Result<Color> {
+    parse_hex_color(hex).ok_or_else(|| Error::ConfigError(format!("Invalid color: {}", hex)))
+}
+
+fn rgb(value: u32) -> Color {
+    Color::RGB((value >> 16) as u8, (value >> 8) as u8, value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_palettes() -> BTreeMap<String, PaletteColors> {
+        BTreeMap::from([(
+            "mine".to_string(),
+            PaletteColors {
+                pixels: vec!["#102030".to_string(), "#405060".to_string()],
+                border: Some("#ffffff".to_string()),
+            },
+        )])
+    }
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#ff8000"), Some(Color::RGB(255, 128, 0)));
+        assert_eq!(parse_hex_color("ff8000"), None);
+        assert_eq!(parse_hex_color("#fff"), None);
+    }
+
+    #[test]
+    fn test_default_palette() {
+        let palette = Palette::default();
+
+        assert_eq!(palette.name, "classic");
+        assert_eq!(palette.pixel(0), Color::BLACK);
+        assert_eq!(palette.foreground(), Color::WHITE);
+    }
+
+    #[test]
+    fn test_find_builtin_palette() {
+        let palette = Palette::find(
+            Some(&PaletteSetting::Name("Amber".to_string())),
+            &BTreeMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(palette.name, "amber");
+        assert_eq!(palette.foreground(), Color::RGB(0xFF, 0xB0, 0x00));
+    }
+
+    #[test]
+    fn test_find_user_palette() {
+        let palette = Palette::find(
+            Some(&PaletteSetting::Name("mine".to_string())),
+            &user_palettes(),
+        )
+        .unwrap();
+
+        assert_eq!(palette.pixel(0), Color::RGB(0x10, 0x20, 0x30));
+        assert_eq!(palette.border, Color::WHITE);
+    }
+
+    #[test]
+    fn test_find_custom_colors() {
+        let setting = PaletteSetting::Colors(vec!["#000000".to_string(), "#00ff00".to_string()]);
+
+        let palette = Palette::find(Some(&setting), &BTreeMap::new()).unwrap();
+
+        assert_eq!(palette.name, "custom");
+        assert_eq!(palette.foreground(), Color::GREEN);
+        // the border defaults to the background
+        assert_eq!(palette.border, Color::BLACK);
+    }
+
+    #[test]
+    fn test_setting() {
+        let colors = PaletteSetting::Colors(vec!["#000000".to_string(), "#00ff00".to_string()]);
+        let amber = PaletteSetting::Name("amber".to_string());
+
+        for setting in [colors, amber] {
+            let palette = Palette::find(Some(&setting), &BTreeMap::new()).unwrap();
+            assert_eq!(palette.setting(), setting);
+        }
+    }
+
+    #[test]
+    fn test_find_returns_err_on_unknown_palette() {
+        let setting = PaletteSetting::Name("not-a-palette".to_string());
+        let res = Palette::find(Some(&setting), &BTreeMap::new());
+        assert!(matches!(res, Err(Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_find_returns_err_on_invalid_colors() {
+        let setting = PaletteSetting::Colors(vec!["#000000".to_string()]);
+        let res = Palette::find(Some(&setting), &BTreeMap::new());
+        assert!(matches!(res, Err(Error::ConfigError(_))));
+
+        let setting = PaletteSetting::Colors(vec!["#000000".to_string(), "green".to_string()]);
+        let res = Palette::find(Some(&setting), &BTreeMap::new());
+        assert!(matches!(res, Err(Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_all_palettes() {
+        let names: Vec<String> = Palette::all(&user_palettes())
+            .unwrap()
+            .into_iter()
+            .map(|palette| palette.name)
+            .collect();
+
+        assert_eq!(names, ["classic", "amber", "green", "lcd", "octo", "mine"]);
+    }
+
+    #[test]
+    fn test_pixel_falls_back_to_foreground() {
+        let setting = PaletteSetting::Colors(vec!["#000000".to_string(), "#00ff00".to_string()]);
+        let palette = Palette::find(Some(&setting), &BTreeMap::new()).unwrap();
+
+        assert_eq!(palette.pixel(0), Color::BLACK);
+        assert_eq!(palette.pixel(3), Color::GREEN);
+    }
+}
@@ -0,0 +1,213 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::cpu::Snapshot;
+use crate::error::{to_sdl_err, Error};
+use crate::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::instruction::Instruction;
+
+// size of the panel, in characters and lines
+const COLUMNS: u32 = 26;
+const ROWS: u32 = 31;
+// in font pixels
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+const MARGIN: u32 = 2;
+// instructions shown before and after the one at PC
+const CONTEXT: i32 = 5;
+const STACK_COLUMNS: usize = 4;
+const BACKGROUND_COLOR: Color = Color::RGB(16, 16, 16);
+const TEXT_COLOR: Color = Color::RGB(200, 200, 200);
+const CURRENT_COLOR: Color = Color::RGB(255, 220, 0);
+// starts the line of the instruction at PC
+const CURRENT_MARKER: char = '>';
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// How many times larger to draw the panel in a window `height` pixels high
+fn scale(height: u32) -> u32 {
+    (height / (ROWS * LINE_HEIGHT + 2 * MARGIN)).max(1)
+}
+
+/// The width of the panel in a window `height` pixels high
+pub fn width(height: u32) -> u32 {
+    (COLUMNS * (GLYPH_WIDTH + 1) + 2 * MARGIN) * scale(height)
+}
+
+/// The machine state as lines of text: registers, timers, stack, keypad
+/// and the instructions around PC
+pub fn lines(machine: &Snapshot) -> Vec<String> {
+    let mut lines = vec![
+        format!("PC {:03X}  I {:03X}", machine.pc, machine.i_register),
+        format!(
+            "SP {:X}  DT {:02X}  ST {:02X}",
+            machine.sp, machine.delay_timer, machine.sound_timer
+        ),
+        String::new(),
+    ];
+
+    let (low, high) = machine.v_registers.split_at(8);
+    for (index, (low, high)) in low.iter().zip(high).enumerate() {
+        lines.push(format!(
+            "V{:X} {:02X}  V{:X} {:02X}",
+            index,
+            low,
+            index + 8,
+            high
+        ));
+    }
+    lines.push(String::new());
+
+    lines.push("STACK".to_string());
+    for (row, addresses) in machine.stack.chunks(STACK_COLUMNS).enumerate() {
+        let entries: Vec<String> = addresses
+            .iter()
+            .enumerate()
+            .map(|(column, address)| {
+                if row * STACK_COLUMNS + column < machine.sp {
+                    format!("{:03X}", address)
+                } else {
+                    "...".to_string()
+                }
+            })
+            .collect();
+        lines.push(entries.join(" "));
+    }
+    lines.push(String::new());
+
+    let keys: String = machine
+        .keypad
+        .iter()
+        .enumerate()
+        .map(|(key, is_pressed)| {
+            if *is_pressed {
+                format!("{:X}", key)
+            } else {
+                ".".to_string()
+            }
+        })
+        .collect();
+    lines.push(format!("KEYS {}", keys));
+    lines.push(String::new());
+
+    for offset in -CONTEXT..=CONTEXT {
+        let address = machine.pc as i32 + 2 * offset;
+        let Some(opcode) = opcode_at(machine, address) else {
+            lines.push(String::new());
+            continue;
+        };
+        let disassembly = Instruction::try_from(opcode).map_or_else(
+            |_| "????".to_string(),
+            |instruction| instruction.disassemble(&machine.quirks),
+        );
+        let marker = if offset == 0 { CURRENT_MARKER } else { ' ' };
+        lines.push(format!(
+            "{} {:03X} {:04X} {}",
+            marker, address, opcode, disassembly
+        ));
+    }
+
+    lines
+}
+
+// the two bytes at an address, if it's in memory
+fn opcode_at(machine: &Snapshot, address: i32) -> Option<u16> {
+    let address = usize::try_from(address).ok()?;
+    let bytes = machine.memory.get(address..address + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Draws the panel over an area of the window
+pub fn draw(canvas: &mut Canvas<Window>, area: Rect, machine: &Snapshot) -> Result<()> {
+    canvas.set_draw_color(BACKGROUND_COLOR);
+    canvas.fill_rect(area).map_err(to_sdl_err)?;
+
+    let scale = scale(area.height());
+    let x = area.x() + (MARGIN * scale) as i32;
+    let mut y = area.y() + (MARGIN * scale) as i32;
+    for line in lines(machine) {
+        let color = if line.starts_with(CURRENT_MARKER) {
+            CURRENT_COLOR
+        } else {
+            TEXT_COLOR
+        };
+        font::draw_text(canvas, &line, (x, y), scale, color)?;
+        y += (LINE_HEIGHT * scale) as i32;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+    use crate::instruction::Key;
+    use rand::rngs::mock::StepRng;
+
+    fn any_machine() -> Snapshot {
+        // V0 = 0x12, I = 0x300, CALL 0x208
+        let mut cpu = CPU::new(StepRng::new(1, 1));
+        cpu.load_rom(&[0x60, 0x12, 0xA3, 0x00, 0x22, 0x08]).unwrap();
+        cpu.set_key_status(Key::try_from(0xA).unwrap(), true);
+        for _ in 0..3 {
+            cpu.tick().unwrap();
+        }
+        cpu.snapshot()
+    }
+
+    #[test]
+    fn test_lines() {
+        let lines = lines(&any_machine());
+
+        assert_eq!(lines.len(), ROWS as usize);
+        assert!(lines
+            .iter()
+            .all(|line| line.chars().count() <= COLUMNS as usize));
+        assert_eq!(lines[0], "PC 208  I 300");
+        assert_eq!(lines[3], "V0 12  V8 00");
+        assert_eq!(lines[13], "206 ... ... ...");
+        assert_eq!(lines[18], "KEYS ..........A.....");
+    }
+
+    #[test]
+    fn test_lines_disassemble_around_pc() {
+        let lines = lines(&any_machine());
+
+        let current: Vec<&String> = lines
+            .iter()
+            .filter(|line| line.starts_with(CURRENT_MARKER))
+            .collect();
+        assert_eq!(current, ["> 208 0000 SYS"]);
+        assert!(lines.contains(&"  204 2208 CALL #208".to_string()));
+    }
+
+    #[test]
+    fn test_lines_disassemble_with_the_quirks() {
+        let mut machine = any_machine();
+        // Bnnn right after the instruction at PC
+        machine.memory[0x20A..0x20C].copy_from_slice(&[0xB3, 0x00]);
+
+        assert!(lines(&machine).contains(&"  20A B300 JP V3, #300".to_string()));
+        machine.quirks.jump = false;
+        assert!(lines(&machine).contains(&"  20A B300 JP V0, #300".to_string()));
+    }
+
+    #[test]
+    fn test_lines_skip_addresses_out_of_memory() {
+        let mut machine = any_machine();
+        machine.pc = 0x002;
+
+        let lines = lines(&machine);
+
+        assert_eq!(lines[20..24], ["", "", "", ""]);
+        assert_eq!(lines[24], "  000 F090 ????");
+    }
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width(320), 160);
+        assert_eq!(width(1080), 640);
+    }
+}
@@ -0,0 +1,230 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::framebuffer::Framebuffer;
+
+const SCREEN_WIDTH: usize = 64;
+const SCREEN_HEIGHT: usize = 32;
+const SCREEN_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+
+pub const LEVEL_ON: u8 = u8::MAX;
+pub const LEVEL_OFF: u8 = 0;
+
+/// How long pixels stay lit after being turned off, to hide the flicker of
+/// sprites being erased and drawn again every frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(try_from = "PersistenceValue", into = "PersistenceValue")]
+pub enum Persistence {
+    #[default]
+    Off,
+    // pixels that turn off fade out over this number of frames
+    Fade(u8),
+    // pixels lit in either of the last two frames are shown
+    Blend,
+}
+
+// Persistence as written in settings files: a mode or a number of frames
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum PersistenceValue {
+    Frames(u8),
+    Mode(String),
+}
+
+impl FromStr for Persistence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "blend" => Ok(Self::Blend),
+            _ => match s.parse() {
+                Ok(0) => Ok(Self::Off),
+                Ok(frames) => Ok(Self::Fade(frames)),
+                Err(_) => Err(format!(
+                    "unknown persistence: {} (off, blend or a number of frames)",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl TryFrom<PersistenceValue> for Persistence {
+    type Error = String;
+
+    fn try_from(value: PersistenceValue) -> Result<Self, Self::Error> {
+        match value {
+            PersistenceValue::Frames(frames) => Self::from_str(&frames.to_string()),
+            PersistenceValue::Mode(mode) => Self::from_str(&mode),
+        }
+    }
+}
+
+impl From<Persistence> for PersistenceValue {
+    fn from(persistence: Persistence) -> Self {
+        match persistence {
+            Persistence::Off => Self::Mode("off".to_string()),
+            Persistence::Fade(frames) => Self::Frames(frames),
+            Persistence::Blend => Self::Mode("blend".to_string()),
+        }
+    }
+}
+
+/// Turns each frame of the CHIP-8 screen into pixel brightness levels, from
+/// `LEVEL_OFF` (background) to `LEVEL_ON` (foreground), emulating the
+/// persistence of a phosphor display.
+pub struct Phosphor {
+    persistence: Persistence,
+    levels: [u8; SCREEN_SIZE],
+    previous: Framebuffer,
+    // whether the levels would change even if the screen didn't
+    is_fading: bool,
+}
+
+impl Phosphor {
+    pub fn new(persistence: Persistence) -> Self {
+        Self {
+            persistence,
+            levels: [LEVEL_OFF; SCREEN_SIZE],
+            previous: Framebuffer::default(),
+            is_fading: false,
+        }
+    }
+
+    pub fn update(&mut self, vmem: &Framebuffer) -> &[u8; SCREEN_SIZE] {
+        match self.persistence {
+            Persistence::Off => {
+                for (level, pixel) in self.levels.iter_mut().zip(vmem.pixels()) {
+                    *level = if pixel { LEVEL_ON } else { LEVEL_OFF };
+                }
+            }
+            Persistence::Fade(frames) => {
+                // enough to reach LEVEL_OFF after `frames` frames
+                let step = (LEVEL_ON as u16 / (frames as u16 + 1) + 1) as u8;
+                for (level, pixel) in self.levels.iter_mut().zip(vmem.pixels()) {
+                    *level = if pixel {
+                        LEVEL_ON
+                    } else {
+                        level.saturating_sub(step)
+                    };
+                }
+            }
+            Persistence::Blend => {
+                for ((level, pixel), previous) in self
+                    .levels
+                    .iter_mut()
+                    .zip(vmem.pixels())
+                    .zip(self.previous.pixels())
+                {
+                    *level = if pixel || previous {
+                        LEVEL_ON
+                    } else {
+                        LEVEL_OFF
+                    };
+                }
+            }
+        }
+
+        self.is_fading = match self.persistence {
+            Persistence::Off => false,
+            Persistence::Fade(_) => self
+                .levels
+                .iter()
+                .any(|level| *level != LEVEL_ON && *level != LEVEL_OFF),
+            Persistence::Blend => self.previous != *vmem,
+        };
+        self.previous = *vmem;
+        &self.levels
+    }
+
+    /// Whether the next update changes the levels even if the screen stays
+    /// the same, i.e. pixels are still fading out
+    pub fn is_fading(&self) -> bool {
+        self.is_fading
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_with_pixel(is_on: bool) -> Framebuffer {
+        let mut rows = [0; SCREEN_HEIGHT];
+        rows[0] = (is_on as u64) << 63;
+        Framebuffer::from(rows)
+    }
+
+    #[test]
+    fn test_off_shows_the_current_frame() {
+        let mut phosphor = Phosphor::new(Persistence::Off);
+
+        assert_eq!(phosphor.update(&screen_with_pixel(true))[0], LEVEL_ON);
+        assert_eq!(phosphor.update(&screen_with_pixel(false))[0], LEVEL_OFF);
+    }
+
+    #[test]
+    fn test_fade_dims_pixels_over_frames() {
+        let mut phosphor = Phosphor::new(Persistence::Fade(2));
+        phosphor.update(&screen_with_pixel(true));
+
+        let first = phosphor.update(&screen_with_pixel(false))[0];
+        let second = phosphor.update(&screen_with_pixel(false))[0];
+        let third = phosphor.update(&screen_with_pixel(false))[0];
+
+        assert!(LEVEL_ON > first && first > second && second > LEVEL_OFF);
+        assert_eq!(third, LEVEL_OFF);
+    }
+
+    #[test]
+    fn test_fade_lights_pixels_at_once() {
+        let mut phosphor = Phosphor::new(Persistence::Fade(4));
+        phosphor.update(&screen_with_pixel(true));
+        phosphor.update(&screen_with_pixel(false));
+
+        assert_eq!(phosphor.update(&screen_with_pixel(true))[0], LEVEL_ON);
+    }
+
+    #[test]
+    fn test_blend_shows_pixels_of_the_last_two_frames() {
+        let mut phosphor = Phosphor::new(Persistence::Blend);
+        phosphor.update(&screen_with_pixel(true));
+
+        assert_eq!(phosphor.update(&screen_with_pixel(false))[0], LEVEL_ON);
+        assert_eq!(phosphor.update(&screen_with_pixel(false))[0], LEVEL_OFF);
+    }
+
+    #[test]
+    fn test_is_fading_until_pixels_settle() {
+        let mut phosphor = Phosphor::new(Persistence::Fade(2));
+        phosphor.update(&screen_with_pixel(true));
+        assert!(!phosphor.is_fading());
+
+        for _ in 0..2 {
+            phosphor.update(&screen_with_pixel(false));
+            assert!(phosphor.is_fading());
+        }
+        phosphor.update(&screen_with_pixel(false));
+        assert!(!phosphor.is_fading());
+    }
+
+    #[test]
+    fn test_blend_is_fading_for_a_frame_after_a_change() {
+        let mut phosphor = Phosphor::new(Persistence::Blend);
+
+        phosphor.update(&screen_with_pixel(true));
+        assert!(phosphor.is_fading());
+        phosphor.update(&screen_with_pixel(true));
+        assert!(!phosphor.is_fading());
+    }
+
+    #[test]
+    fn test_persistence_from_str() {
+        assert_eq!("off".parse(), Ok(Persistence::Off));
+        assert_eq!("blend".parse(), Ok(Persistence::Blend));
+        assert_eq!("3".parse(), Ok(Persistence::Fade(3)));
+        assert_eq!("0".parse(), Ok(Persistence::Off));
+        assert!("glow".parse::<Persistence>().is_err());
+    }
+}
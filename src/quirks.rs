@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Behaviours that differ between CHIP-8 implementations. Names and meaning
+/// follow the quirks of the CHIP-8 database (see `database/`).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Quirks {
+    // 8xy6 / 8xyE shift Vx in place instead of Vy
+    pub shift: bool,
+    // Fx55 / Fx65 increment I by x instead of x + 1
+    pub memory_increment_by_x: bool,
+    // Fx55 / Fx65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    // sprites wrap around the screen edges instead of being clipped
+    pub wrap: bool,
+    // Bxnn jumps to xnn + Vx instead of nnn + V0
+    pub jump: bool,
+    // Dxyn waits for the next frame before executing more instructions
+    pub vblank: bool,
+    // 8xy1 / 8xy2 / 8xy3 reset VF to zero
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    // Super-CHIP behaviour for ambiguous opcodes
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: true,
+            jump: true,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+impl Quirks {
+    pub fn with_overrides(self, overrides: &QuirkOverrides) -> Self {
+        Self {
+            shift: overrides.shift.unwrap_or(self.shift),
+            memory_increment_by_x: overrides
+                .memory_increment_by_x
+                .unwrap_or(self.memory_increment_by_x),
+            memory_leave_i_unchanged: overrides
+                .memory_leave_i_unchanged
+                .unwrap_or(self.memory_leave_i_unchanged),
+            wrap: overrides.wrap.unwrap_or(self.wrap),
+            jump: overrides.jump.unwrap_or(self.jump),
+            vblank: overrides.vblank.unwrap_or(self.vblank),
+            logic: overrides.logic.unwrap_or(self.logic),
+        }
+    }
+}
+
+/// A partial set of quirks, where missing quirks keep their current value.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuirkOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_increment_by_x: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_leave_i_unchanged: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jump: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vblank: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logic: Option<bool>,
+}
+
+impl QuirkOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fills the quirks missing here with the ones from `other`
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            shift: self.shift.or(other.shift),
+            memory_increment_by_x: self.memory_increment_by_x.or(other.memory_increment_by_x),
+            memory_leave_i_unchanged: self
+                .memory_leave_i_unchanged
+                .or(other.memory_leave_i_unchanged),
+            wrap: self.wrap.or(other.wrap),
+            jump: self.jump.or(other.jump),
+            vblank: self.vblank.or(other.vblank),
+            logic: self.logic.or(other.logic),
+        }
+    }
+
+    /// Sets a quirk by its name in the CHIP-8 database (i.e. "memoryIncrementByX")
+    pub fn set(&mut self, name: &str, value: bool) -> Result<(), Error> {
+        let quirk = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            _ => return Err(Error::ConfigError(format!("Unknown quirk: {}", name))),
+        };
+        *quirk = Some(value);
+        Ok(())
+    }
+}
+
+impl From<Quirks> for QuirkOverrides {
+    fn from(quirks: Quirks) -> Self {
+        Self {
+            shift: Some(quirks.shift),
+            memory_increment_by_x: Some(quirks.memory_increment_by_x),
+            memory_leave_i_unchanged: Some(quirks.memory_leave_i_unchanged),
+            wrap: Some(quirks.wrap),
+            jump: Some(quirks.jump),
+            vblank: Some(quirks.vblank),
+            logic: Some(quirks.logic),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_overrides() {
+        let overrides = QuirkOverrides {
+            shift: Some(false),
+            vblank: Some(true),
+            ..QuirkOverrides::default()
+        };
+
+        let quirks = Quirks::default().with_overrides(&overrides);
+
+        assert_eq!(
+            quirks,
+            Quirks {
+                shift: false,
+                vblank: true,
+                ..Quirks::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_overrides_or() {
+        let high = QuirkOverrides {
+            shift: Some(false),
+            ..QuirkOverrides::default()
+        };
+        let low = QuirkOverrides {
+            shift: Some(true),
+            jump: Some(false),
+            ..QuirkOverrides::default()
+        };
+
+        let overrides = high.or(low);
+
+        assert_eq!(overrides.shift, Some(false));
+        assert_eq!(overrides.jump, Some(false));
+        assert_eq!(overrides.wrap, None);
+    }
+
+    #[test]
+    fn test_overrides_set() {
+        let mut overrides = QuirkOverrides::default();
+
+        overrides.set("memoryIncrementByX", true).unwrap();
+
+        assert_eq!(overrides.memory_increment_by_x, Some(true));
+        assert!(matches!(
+            overrides.set("not-a-quirk", true),
+            Err(Error::ConfigError(_))
+        ));
+    }
+}
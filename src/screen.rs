@@ -1,33 +1,47 @@
-use sdl2::pixels::PixelFormatEnum;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
 use sdl2::render::{Canvas, Texture, TextureCreator};
 use sdl2::video::{Window, WindowContext};
 
-use crate::error::Error;
+use crate::config::Config;
+use crate::error::{to_sdl_err, Error};
+use crate::filters::{Filter, Rgb};
+use crate::framebuffer::Framebuffer;
+use crate::palette::Palette;
+use crate::phosphor::{Phosphor, LEVEL_ON};
 
-const SCALE: usize = 10;
+pub const DEFAULT_SCALE: u32 = 10;
+pub const WINDOW_TITLE: &str = "CHIP-8 by ladybenko";
 const SCREEN_WIDTH: usize = 64;
 const SCREEN_HEIGHT: usize = 32;
-const BUFFER_SIZE: usize = 3 * SCREEN_WIDTH * SCREEN_HEIGHT;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The size of a window showing the CHIP-8 screen `scale` times larger
+/// The size of a window showing the screen `scale` times larger, unless it
+/// overflows
+pub fn window_size(scale: u32) -> Option<(u32, u32)> {
+    Some((
+        (SCREEN_WIDTH as u32).checked_mul(scale)?,
+        (SCREEN_HEIGHT as u32).checked_mul(scale)?,
+    ))
+}
+
 pub fn build_canvas_and_creator(
     context: &sdl2::Sdl,
+    config: &Config,
 ) -> Result<(Canvas<Window>, TextureCreator<WindowContext>)> {
     let video_system = context.video().map_err(to_sdl_err)?;
-    let window = video_system
-        .window(
-            "CHIP-8 by ladybenko",
-            (SCREEN_WIDTH * SCALE) as u32,
-            (SCREEN_HEIGHT * SCALE) as u32,
-        )
-        .position_centered()
-        .build()?;
-    let mut canvas = window.into_canvas().present_vsync().build()?;
-
-    canvas
-        .set_scale(SCALE as f32, SCALE as f32)
-        .map_err(to_sdl_err)?;
+    let (width, height) = config.window_size;
+    let mut window = video_system.window(WINDOW_TITLE, width, height);
+    window.position_centered().resizable();
+    if config.fullscreen {
+        window.fullscreen_desktop();
+    }
+    let mut window = window.build()?;
+    window.set_minimum_size(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)?;
+    // without vsync, as the run loop paces frames itself
+    let canvas = window.into_canvas().build()?;
 
     let texture_creator = canvas.texture_creator();
 
@@ -36,57 +50,171 @@ pub fn build_canvas_and_creator(
 
 pub struct Screen<'a> {
     pub texture: Texture<'a>,
-    pub buffer: [u8; BUFFER_SIZE],
+    pub buffer: Vec<u8>,
+    palette: Palette,
+    phosphor: Phosphor,
+    filter: Filter,
+    is_integer_scale: bool,
+    // the screen generation of the CPU shown in the texture, if it's up to date
+    generation: Option<u64>,
 }
 
 impl<'a> Screen<'a> {
+    pub fn new(
+        texture_creator: &'a TextureCreator<WindowContext>,
+        config: &Config,
+    ) -> Result<Self> {
+        let factor = config.filter.factor();
+        let texture = texture_creator.create_texture_target(
+            PixelFormatEnum::RGB24,
+            (SCREEN_WIDTH * factor) as u32,
+            (SCREEN_HEIGHT * factor) as u32,
+        )?;
+
+        Ok(Self {
+            texture,
+            buffer: vec![0; 3 * SCREEN_WIDTH * SCREEN_HEIGHT * factor * factor],
+            palette: config.palette.clone(),
+            phosphor: Phosphor::new(config.persistence),
+            filter: config.filter,
+            is_integer_scale: config.integer_scale,
+            generation: None,
+        })
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.generation = None;
+    }
+
+    /// Draws the screen in an area of the window, where `generation` is the
+    /// CPU's screen generation: the texture is only updated when it changes.
+    /// Returns the part of the window the screen is drawn in, for overlays
+    /// to be drawn on before the canvas is presented.
     pub fn frame(
         &mut self,
         canvas: &mut Canvas<Window>,
-        vmem: &[bool; SCREEN_WIDTH * SCREEN_HEIGHT],
-    ) -> Result<()> {
-        if self.update_screen_buffer(vmem) {
-            self.texture.update(None, &self.buffer, SCREEN_WIDTH * 3)?;
-            canvas.copy(&self.texture, None, None).map_err(to_sdl_err)?;
-            canvas.present();
+        vmem: &Framebuffer,
+        generation: u64,
+        area: Rect,
+    ) -> Result<Rect> {
+        if self.update_screen_buffer(vmem, generation) {
+            let pitch = SCREEN_WIDTH * self.filter.factor() * 3;
+            self.texture.update(None, &self.buffer, pitch)?;
         }
 
-        Ok(())
-    }
+        // the contents of the canvas are undefined after presenting, so the
+        // texture is copied every frame even if it didn't change
+        let mut viewport = viewport(area.width(), area.height(), self.is_integer_scale);
+        viewport.offset(area.x(), area.y());
+
+        // the border shows around the screen when letterboxed
+        canvas.set_draw_color(self.palette.border);
+        canvas.clear();
+        canvas
+            .copy(&self.texture, None, viewport)
+            .map_err(to_sdl_err)?;
 
-    fn update_screen_buffer(&mut self, vmem: &[bool; SCREEN_WIDTH * SCREEN_HEIGHT]) -> bool {
-        for i in 0..(SCREEN_WIDTH * SCREEN_HEIGHT) {
-            let (red, green, blue) = ((i * 3), (i * 3 + 1), (i * 3 + 2));
+        Ok(viewport)
+    }
 
-            self.buffer[red] = if vmem[i] { 0xFF } else { 0x00 };
-            self.buffer[green] = if vmem[i] { 0xFF } else { 0x00 };
-            self.buffer[blue] = if vmem[i] { 0xFF } else { 0x00 };
+    fn update_screen_buffer(&mut self, vmem: &Framebuffer, generation: u64) -> bool {
+        if self.generation == Some(generation) && !self.phosphor.is_fading() {
+            return false;
+        }
+        self.generation = Some(generation);
+
+        let background = self.palette.pixel(0);
+        let foreground = self.palette.pixel(1);
+
+        let pixels: Vec<Rgb> = self
+            .phosphor
+            .update(vmem)
+            .iter()
+            .map(|level| {
+                let color = blend(background, foreground, *level);
+                [color.r, color.g, color.b]
+            })
+            .collect();
+
+        let filtered = self.filter.apply(&pixels, SCREEN_WIDTH, SCREEN_HEIGHT);
+        for (rgb, pixel) in self.buffer.chunks_exact_mut(3).zip(filtered) {
+            rgb.copy_from_slice(&pixel);
         }
 
         true
     }
 }
 
-impl<'a> TryFrom<&'a TextureCreator<WindowContext>> for Screen<'a> {
-    type Error = Error;
+/// The largest area of a `width` x `height` window that keeps the aspect
+/// ratio of the CHIP-8 screen, centered. With `is_integer`, the screen is
+/// only scaled by whole numbers so all pixels have the same size.
+pub fn viewport(width: u32, height: u32, is_integer: bool) -> Rect {
+    let scale = f64::min(
+        width as f64 / SCREEN_WIDTH as f64,
+        height as f64 / SCREEN_HEIGHT as f64,
+    );
+    let scale = if is_integer {
+        scale.floor().max(1.0)
+    } else {
+        scale
+    };
+
+    let (viewport_width, viewport_height) = (
+        (SCREEN_WIDTH as f64 * scale) as u32,
+        (SCREEN_HEIGHT as f64 * scale) as u32,
+    );
+    let x = (width as i32 - viewport_width as i32) / 2;
+    let y = (height as i32 - viewport_height as i32) / 2;
+
+    Rect::new(x, y, viewport_width, viewport_height)
+}
 
-    fn try_from(
-        texture_creator: &'a TextureCreator<WindowContext>,
-    ) -> std::result::Result<Self, Self::Error> {
-        // let creator = canvas.texture_creator();
-        let texture = texture_creator.create_texture_target(
-            PixelFormatEnum::RGB24,
-            SCREEN_WIDTH as u32,
-            SCREEN_HEIGHT as u32,
-        )?;
+/// Mixes two colors, from all `from` at level 0 to all `to` at `LEVEL_ON`
+fn blend(from: Color, to: Color, level: u8) -> Color {
+    let mix = |from: u8, to: u8| {
+        let (from, to, level) = (from as u32, to as u32, level as u32);
+        ((from * (LEVEL_ON as u32 - level) + to * level) / LEVEL_ON as u32) as u8
+    };
 
-        Ok(Self {
-            texture,
-            buffer: [0; BUFFER_SIZE],
-        })
-    }
+    Color::RGB(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
 }
 
-fn to_sdl_err(err: String) -> Error {
-    Error::SystemError(err)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewport_fills_a_window_with_the_same_aspect_ratio() {
+        assert_eq!(viewport(640, 320, false), Rect::new(0, 0, 640, 320));
+    }
+
+    #[test]
+    fn test_viewport_is_letterboxed() {
+        // wider window
+        assert_eq!(viewport(800, 320, false), Rect::new(80, 0, 640, 320));
+        // taller window
+        assert_eq!(viewport(640, 480, false), Rect::new(0, 80, 640, 320));
+    }
+
+    #[test]
+    fn test_viewport_with_integer_scale() {
+        assert_eq!(viewport(700, 400, false), Rect::new(0, 25, 700, 350));
+        assert_eq!(viewport(700, 400, true), Rect::new(30, 40, 640, 320));
+    }
+
+    #[test]
+    fn test_viewport_in_a_window_smaller_than_the_screen() {
+        assert_eq!(viewport(32, 16, true), Rect::new(-16, -8, 64, 32));
+    }
+
+    #[test]
+    fn test_blend() {
+        let from = Color::RGB(0, 100, 255);
+        let to = Color::RGB(255, 0, 255);
+
+        assert_eq!(blend(from, to, 0), from);
+        assert_eq!(blend(from, to, LEVEL_ON), to);
+        assert_eq!(blend(from, to, 51), Color::RGB(51, 80, 255));
+    }
 }
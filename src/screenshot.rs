@@ -0,0 +1,176 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::Error;
+use crate::framebuffer::Framebuffer;
+use crate::palette::Palette;
+
+const SCREEN_WIDTH: usize = 64;
+const SCREEN_HEIGHT: usize = 32;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    Png,
+    Ppm,
+}
+
+impl Format {
+    /// The format matching the extension of a file, ".png" or ".ppm"
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("png") => Ok(Self::Png),
+            Some(extension) if extension.eq_ignore_ascii_case("ppm") => Ok(Self::Ppm),
+            _ => Err(Error::ConfigError(format!(
+                "Unsupported image format: {} (expected a .png or .ppm file)",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// An RGB image of the CHIP-8 screen, scaled up by a whole number
+#[derive(Debug, PartialEq, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    // row by row, 3 bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(framebuffer: &Framebuffer, palette: &Palette, scale: u32) -> Self {
+        let scale = scale as usize;
+        let width = SCREEN_WIDTH * scale;
+        let height = SCREEN_HEIGHT * scale;
+
+        let colors = [palette.pixel(0), palette.pixel(1)];
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let color = colors[framebuffer.pixel(x / scale, y / scale) as usize];
+                pixels.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+
+        Self {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+        }
+    }
+
+    /// Saves the image as PNG or PPM, depending on the extension of `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let format = Format::from_path(path)?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            Format::Png => self.write_png(&mut writer)?,
+            Format::Ppm => self.write_ppm(&mut writer)?,
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn write_png<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+
+        Ok(())
+    }
+
+    fn write_ppm<W: Write>(&self, mut writer: W) -> Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.pixels)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn any_image(scale: u32) -> Image {
+        let mut framebuffer = Framebuffer::default();
+        framebuffer.draw_sprite(&[0x80], 1, 0, false);
+        Image::new(&framebuffer, &Palette::default(), scale)
+    }
+
+    fn rgb_at(image: &Image, x: u32, y: u32) -> &[u8] {
+        let offset = ((y * image.width + x) * 3) as usize;
+        &image.pixels[offset..offset + 3]
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        let format = |path: &str| Format::from_path(&PathBuf::from(path));
+
+        assert!(matches!(format("out.ppm"), Ok(Format::Ppm)));
+        assert!(matches!(format("out.PPM"), Ok(Format::Ppm)));
+        assert!(matches!(format("out.png"), Ok(Format::Png)));
+        assert!(matches!(format("out.jpg"), Err(Error::ConfigError(_))));
+        assert!(matches!(format("out"), Err(Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_new_applies_the_palette_and_scale() {
+        let palette = Palette::default();
+        let background = palette.pixel(0);
+        let foreground = palette.pixel(1);
+
+        let image = any_image(3);
+
+        assert_eq!((image.width, image.height), (192, 96));
+        assert_eq!(image.pixels.len(), 192 * 96 * 3);
+        assert_eq!(
+            rgb_at(&image, 2, 2),
+            [background.r, background.g, background.b]
+        );
+        for (x, y) in [(3, 0), (5, 2)] {
+            assert_eq!(
+                rgb_at(&image, x, y),
+                [foreground.r, foreground.g, foreground.b]
+            );
+        }
+        assert_eq!(
+            rgb_at(&image, 6, 0),
+            [background.r, background.g, background.b]
+        );
+    }
+
+    #[test]
+    fn test_write_ppm() {
+        let image = any_image(1);
+        let mut bytes = vec![];
+
+        image.write_ppm(&mut bytes).unwrap();
+
+        let header = b"P6\n64 32\n255\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(&bytes[header.len()..], image.pixels.as_slice());
+    }
+
+    #[test]
+    fn test_write_png() {
+        let image = any_image(2);
+        let mut bytes = vec![];
+
+        image.write_png(&mut bytes).unwrap();
+
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (128, 64));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(pixels, image.pixels);
+    }
+}
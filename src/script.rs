@@ -0,0 +1,145 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+const KEY_COUNT: u8 = 16;
+
+/// CHIP-8 keys held down at given frames, to play a ROM without a window. It
+/// is written as a comma-separated list of `KEY@FRAME` (held for one frame)
+/// or `KEY@START..END` (held from START until END, excluded), where keys are
+/// hex digits, i.e. "5@10..20,A@60".
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct KeyScript {
+    presses: Vec<(u8, Range<u32>)>,
+}
+
+impl KeyScript {
+    pub fn is_pressed(&self, key: u8, frame: u32) -> bool {
+        self.presses
+            .iter()
+            .any(|(pressed, frames)| *pressed == key && frames.contains(&frame))
+    }
+
+    /// Loads an input movie: a script in a file, where the lines starting
+    /// with '#' are comments
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)?;
+        let script: Vec<&str> = contents
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .collect();
+
+        script
+            .join("\n")
+            .parse()
+            .map_err(|err| Error::ConfigError(format!("{}: {}", path.display(), err)))
+    }
+
+    /// The state of all keys at a frame, indexed by key
+    pub fn keys_at(&self, frame: u32) -> [bool; KEY_COUNT as usize] {
+        let mut keys = [false; KEY_COUNT as usize];
+        for (key, is_pressed) in keys.iter_mut().enumerate() {
+            *is_pressed = self.is_pressed(key as u8, frame);
+        }
+        keys
+    }
+}
+
+impl FromStr for KeyScript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let presses = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty())
+            .map(parse_press)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { presses })
+    }
+}
+
+fn parse_press(entry: &str) -> Result<(u8, Range<u32>), String> {
+    let (key, frames) = entry
+        .split_once('@')
+        .ok_or_else(|| format!("expected KEY@FRAME, got {}", entry))?;
+
+    let key = u8::from_str_radix(key, 16)
+        .ok()
+        .filter(|key| *key < KEY_COUNT)
+        .ok_or_else(|| format!("invalid key: {} (0 to F)", key))?;
+
+    let parse_frame = |frame: &str| {
+        frame
+            .parse::<u32>()
+            .map_err(|_| format!("invalid frame: {}", frame))
+    };
+    let frames = match frames.split_once("..") {
+        Some((start, end)) => parse_frame(start)?..parse_frame(end)?,
+        None => {
+            let frame = parse_frame(frames)?;
+            frame..frame + 1
+        }
+    };
+    if frames.is_empty() {
+        return Err(format!("empty frame range: {}", entry));
+    }
+
+    Ok((key, frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        let script: KeyScript = "5@10..20, a@60".parse().unwrap();
+
+        assert_eq!(script.presses, vec![(0x5, 10..20), (0xA, 60..61)]);
+        assert_eq!("".parse(), Ok(KeyScript::default()));
+    }
+
+    #[test]
+    fn test_from_str_returns_err_on_invalid_entries() {
+        for script in ["5", "G@10", "10@1", "5@x", "5@20..10"] {
+            assert!(script.parse::<KeyScript>().is_err(), "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("chip8-rs-{}.keys", std::process::id()));
+        fs::write(&path, "# jump\n5@10..20\n\nA@60, 4@70\n").unwrap();
+
+        let script = KeyScript::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(script.unwrap(), "5@10..20,A@60,4@70".parse().unwrap());
+    }
+
+    #[test]
+    fn test_is_pressed() {
+        let script: KeyScript = "5@10..12,5@20".parse().unwrap();
+
+        assert!(!script.is_pressed(0x5, 9));
+        assert!(script.is_pressed(0x5, 10));
+        assert!(script.is_pressed(0x5, 11));
+        assert!(!script.is_pressed(0x5, 12));
+        assert!(script.is_pressed(0x5, 20));
+        assert!(!script.is_pressed(0x6, 10));
+    }
+
+    #[test]
+    fn test_keys_at() {
+        let script: KeyScript = "1@0,F@0..2".parse().unwrap();
+
+        let keys = script.keys_at(0);
+
+        assert!(keys[0x1] && keys[0xF]);
+        assert_eq!(keys.iter().filter(|is_pressed| **is_pressed).count(), 2);
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::Waveform;
+use crate::database::RomInfo;
+use crate::error::Error;
+use crate::filters::Filter;
+use crate::palette::{PaletteColors, PaletteSetting};
+use crate::phosphor::Persistence;
+use crate::quirks::QuirkOverrides;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+const CONFIG_DIR: &str = "chip8-rs";
+const CONFIG_FILE: &str = "config.toml";
+const ROMS_DIR: &str = "roms";
+const WINDOW_FILE: &str = "window.toml";
+
+/// Where to save the settings of a ROM
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Location {
+    // next to the ROM, as `<rom file name>.toml`
+    Sidecar,
+    // in the config directory, as `roms/<SHA-1 of the ROM>.toml`
+    Config,
+}
+
+/// A partial set of settings, read from (and saved to) TOML files. Settings
+/// are layered, and the ones missing from a layer are taken from the next:
+///
+/// 1. command line flags
+/// 2. the ROM sidecar file, then the ROM file in the config directory
+/// 3. the CHIP-8 database
+/// 4. the global config file
+#[derive(Debug, PartialEq, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    // instructions per timer tick (60 Hz), as in the CHIP-8 database
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tickrate: Option<u32>,
+    // instructions per second, which take precedence over the tickrate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ips: Option<u32>,
+    // run the CPU on its own thread
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threaded: Option<bool>,
+    // how many times faster to run while fast-forwarding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fast_forward: Option<u32>,
+    // show the frames and instructions per second over the screen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_fps: Option<bool>,
+    // keymap preset or path to a keymap file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keymap: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_key_release: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rumble: Option<bool>,
+    // palette name, or list of "#rrggbb" colors (background first)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<PaletteSetting>,
+    // "off", "blend" or the number of frames pixels take to fade out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistence: Option<Persistence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Filter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fullscreen: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integer_scale: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    // buzzer frequency, in Hz
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tone: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub waveform: Option<Waveform>,
+    #[serde(skip_serializing_if = "QuirkOverrides::is_empty")]
+    pub quirks: QuirkOverrides,
+    // game actions ("up", "a", ...) to CHIP-8 keys
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub actions: BTreeMap<String, u8>,
+    // user-defined palettes, by name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub palettes: BTreeMap<String, PaletteColors>,
+}
+
+impl Settings {
+    /// Fills the settings missing here with the ones from `other`
+    pub fn or(self, other: Self) -> Self {
+        let mut actions = other.actions;
+        actions.extend(self.actions);
+        let mut palettes = other.palettes;
+        palettes.extend(self.palettes);
+
+        // the tickrate and instructions per second both set the speed, so
+        // they are taken together from the same layer
+        let (tickrate, ips) = if self.tickrate.is_some() || self.ips.is_some() {
+            (self.tickrate, self.ips)
+        } else {
+            (other.tickrate, other.ips)
+        };
+
+        Self {
+            tickrate,
+            ips,
+            threaded: self.threaded.or(other.threaded),
+            fast_forward: self.fast_forward.or(other.fast_forward),
+            show_fps: self.show_fps.or(other.show_fps),
+            keymap: self.keymap.or(other.keymap),
+            wait_key_release: self.wait_key_release.or(other.wait_key_release),
+            rumble: self.rumble.or(other.rumble),
+            palette: self.palette.or(other.palette),
+            persistence: self.persistence.or(other.persistence),
+            filter: self.filter.or(other.filter),
+            scale: self.scale.or(other.scale),
+            fullscreen: self.fullscreen.or(other.fullscreen),
+            integer_scale: self.integer_scale.or(other.integer_scale),
+            fps: self.fps.or(other.fps),
+            volume: self.volume.or(other.volume),
+            tone: self.tone.or(other.tone),
+            waveform: self.waveform.or(other.waveform),
+            quirks: self.quirks.or(other.quirks),
+            actions,
+            palettes,
+        }
+    }
+
+    /// Reads a settings file, returning `None` if it doesn't exist
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        toml::from_str(&source)
+            .map(Some)
+            .map_err(|err| Error::ConfigError(format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Reads the global config file, which holds the defaults for every ROM
+    pub fn load_global() -> Result<Self> {
+        match config_dir() {
+            Some(dir) => Ok(Self::load(dir.join(CONFIG_FILE))?.unwrap_or_default()),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Reads the settings saved for a ROM, where the sidecar file takes
+    /// precedence over the one in the config directory
+    pub fn load_for_rom(rom: &Path, hash: &str) -> Result<Self> {
+        let sidecar = Self::load(sidecar_path(rom))?.unwrap_or_default();
+        let saved = match rom_config_path(hash) {
+            Some(path) => Self::load(path)?.unwrap_or_default(),
+            None => Self::default(),
+        };
+
+        Ok(sidecar.or(saved))
+    }
+}
+
+/// The size of the window when it was last closed, used unless a scale is
+/// given in the settings
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowSize {
+    pub fn load() -> Result<Option<Self>> {
+        let path = match config_dir() {
+            Some(dir) => dir.join(WINDOW_FILE),
+            None => return Ok(None),
+        };
+
+        match fs::read_to_string(path) {
+            Ok(source) => Ok(Some(toml::from_str(&source)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = config_dir()
+            .ok_or_else(|| Error::ConfigError("Could not find the config directory".to_string()))?;
+        fs::create_dir_all(&dir)?;
+
+        fs::write(dir.join(WINDOW_FILE), toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+impl From<&RomInfo> for Settings {
+    fn from(info: &RomInfo) -> Self {
+        Self {
+            tickrate: info.tickrate,
+            palette: info
+                .colors
+                .as_ref()
+                .map(|colors| PaletteSetting::Colors(colors.pixels.clone())),
+            quirks: info.quirks.into(),
+            actions: info.keys.clone().into_iter().collect(),
+            ..Self::default()
+        }
+    }
+}
+
+/// The chip8-rs folder in the user's config directory (i.e. `~/.config/chip8-rs`)
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR))
+}
+
+pub fn sidecar_path(rom: &Path) -> PathBuf {
+    let mut path = rom.as_os_str().to_owned();
+    path.push(".toml");
+    path.into()
+}
+
+pub fn rom_config_path(hash: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(ROMS_DIR).join(format!("{}.toml", hash)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    #[test]
+    fn test_or_keeps_settings_in_precedence_order() {
+        let high = Settings {
+            tickrate: Some(20),
+            actions: BTreeMap::from([("a".to_string(), 0x5)]),
+            ..Settings::default()
+        };
+        let low = Settings {
+            tickrate: Some(10),
+            scale: Some(4),
+            actions: BTreeMap::from([("a".to_string(), 0x6), ("b".to_string(), 0x4)]),
+            ..Settings::default()
+        };
+
+        let settings = high.or(low);
+
+        assert_eq!(settings.tickrate, Some(20));
+        assert_eq!(settings.scale, Some(4));
+        assert_eq!(settings.volume, None);
+        assert_eq!(
+            settings.actions,
+            BTreeMap::from([("a".to_string(), 0x5), ("b".to_string(), 0x4)])
+        );
+    }
+
+    #[test]
+    fn test_or_takes_the_speed_from_a_single_layer() {
+        let high = Settings {
+            tickrate: Some(20),
+            ..Settings::default()
+        };
+        let low = Settings {
+            ips: Some(1000),
+            ..Settings::default()
+        };
+
+        let settings = high.or(low);
+
+        assert_eq!(settings.tickrate, Some(20));
+        assert_eq!(settings.ips, None);
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let settings: Settings = toml::from_str(
+            r##"
+            tickrate = 15
+            keymap = "numpad"
+            waveform = "triangle"
+            palette = ["#000000", "#00ff00"]
+
+            [quirks]
+            vblank = true
+
+            [palettes.mine]
+            pixels = ["#000000", "#ffffff"]
+
+            [actions]
+            up = 5
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings,
+            Settings {
+                tickrate: Some(15),
+                keymap: Some("numpad".to_string()),
+                waveform: Some(Waveform::Triangle),
+                palette: Some(PaletteSetting::Colors(vec![
+                    "#000000".to_string(),
+                    "#00ff00".to_string()
+                ])),
+                quirks: QuirkOverrides {
+                    vblank: Some(true),
+                    ..QuirkOverrides::default()
+                },
+                actions: BTreeMap::from([("up".to_string(), 5)]),
+                palettes: BTreeMap::from([(
+                    "mine".to_string(),
+                    PaletteColors {
+                        pixels: vec!["#000000".to_string(), "#ffffff".to_string()],
+                        border: None,
+                    }
+                )]),
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_palette_by_name_from_toml() {
+        let settings: Settings = toml::from_str("palette = \"amber\"").unwrap();
+        assert_eq!(
+            settings.palette,
+            Some(PaletteSetting::Name("amber".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_persistence_from_toml() {
+        let settings: Settings = toml::from_str("persistence = 4").unwrap();
+        assert_eq!(settings.persistence, Some(Persistence::Fade(4)));
+
+        let settings: Settings = toml::from_str("persistence = \"blend\"").unwrap();
+        assert_eq!(settings.persistence, Some(Persistence::Blend));
+
+        let res = toml::from_str::<Settings>("persistence = \"glow\"");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_to_toml_skips_missing_settings() {
+        let settings = Settings {
+            scale: Some(8),
+            ..Settings::default()
+        };
+
+        assert_eq!(toml::to_string_pretty(&settings).unwrap(), "scale = 8\n");
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir()
+            .join("chip8-rs-test-settings")
+            .join("game.ch8.toml");
+        let settings = Settings {
+            tickrate: Some(30),
+            quirks: Quirks::default().into(),
+            ..Settings::default()
+        };
+
+        settings.save(&path).unwrap();
+        let loaded = Settings::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Some(settings));
+    }
+
+    #[test]
+    fn test_load_returns_none_on_missing_file() {
+        let res = Settings::load("not-a-settings-file.toml");
+        assert!(matches!(res, Ok(None)));
+    }
+
+    #[test]
+    fn test_sidecar_path() {
+        assert_eq!(
+            sidecar_path(Path::new("roms/game.ch8")),
+            PathBuf::from("roms/game.ch8.toml")
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use gif::{Encoder, Repeat};
+
+use crate::config::TIMER_HZ;
+use crate::error::Error;
+use crate::framebuffer::Framebuffer;
+use crate::palette::Palette;
+use crate::screenshot::Image;
+
+const SCREEN_WIDTH: usize = 64;
+const SCREEN_HEIGHT: usize = 32;
+// browsers slow down GIF frames shorter than this, in centiseconds
+const MIN_GIF_DELAY: u64 = 2;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Records the CHIP-8 screen to an animated GIF or, for any other path, to a
+/// directory of PNG files named after the frame (at 60 Hz) they show up in.
+/// Only the frames where the screen changed are kept.
+pub struct VideoRecorder {
+    path: PathBuf,
+    sink: Sink,
+    palette: Palette,
+    scale: usize,
+    // the last frame shown, and since when
+    last: Option<(Framebuffer, Duration)>,
+}
+
+enum Sink {
+    Gif {
+        encoder: Encoder<BufWriter<File>>,
+        // length of the frames written, in centiseconds
+        written: u64,
+    },
+    Images {
+        directory: PathBuf,
+    },
+}
+
+impl VideoRecorder {
+    pub fn create(path: &Path, palette: &Palette, scale: u32) -> Result<Self> {
+        let scale = scale as usize;
+
+        let is_gif = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+        let sink = if is_gif {
+            let colors: Vec<u8> = [palette.pixel(0), palette.pixel(1)]
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b])
+                .collect();
+            let mut encoder = Encoder::new(
+                BufWriter::new(File::create(path)?),
+                (SCREEN_WIDTH * scale) as u16,
+                (SCREEN_HEIGHT * scale) as u16,
+                &colors,
+            )?;
+            encoder.set_repeat(Repeat::Infinite)?;
+
+            Sink::Gif {
+                encoder,
+                written: 0,
+            }
+        } else {
+            fs::create_dir_all(path)?;
+            Sink::Images {
+                directory: path.to_path_buf(),
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            sink,
+            palette: palette.clone(),
+            scale,
+            last: None,
+        })
+    }
+
+    /// Records the screen at `time` since the start of the recording, if it
+    /// changed since the last frame
+    pub fn push(&mut self, framebuffer: &Framebuffer, time: Duration) -> Result<()> {
+        if let Some((last, since)) = &self.last {
+            if last == framebuffer {
+                return Ok(());
+            }
+
+            if let Sink::Gif { written, .. } = &self.sink {
+                // frames too short to show are dropped, and the next one
+                // takes their place
+                if centiseconds(time) < *written + MIN_GIF_DELAY {
+                    self.last = Some((*framebuffer, *since));
+                    return Ok(());
+                }
+            }
+            self.write_last(time)?;
+        }
+
+        if let Sink::Images { directory } = &self.sink {
+            let frame = (time.as_secs_f64() * TIMER_HZ).round() as u64;
+            let path = directory.join(format!("{:06}.png", frame));
+            Image::new(framebuffer, &self.palette, self.scale as u32).save(&path)?;
+        }
+        self.last = Some((*framebuffer, time));
+
+        Ok(())
+    }
+
+    /// Writes the last frame, shown until `time`, and closes the recording,
+    /// returning its path
+    pub fn finish(mut self, time: Duration) -> Result<PathBuf> {
+        if self.last.is_some() {
+            self.write_last(time)?;
+        }
+
+        if let Sink::Gif { encoder, .. } = self.sink {
+            encoder.into_inner()?.flush()?;
+        }
+
+        Ok(self.path)
+    }
+
+    // writes the last GIF frame, shown until `time`
+    fn write_last(&mut self, time: Duration) -> Result<()> {
+        let (Sink::Gif { encoder, written }, Some((last, _))) = (&mut self.sink, &self.last) else {
+            return Ok(());
+        };
+
+        let delay = centiseconds(time)
+            .saturating_sub(*written)
+            .max(MIN_GIF_DELAY);
+        let mut frame = gif::Frame::from_indexed_pixels(
+            (SCREEN_WIDTH * self.scale) as u16,
+            (SCREEN_HEIGHT * self.scale) as u16,
+            indices(last, self.scale),
+            None,
+        );
+        frame.delay = delay as u16;
+        encoder.write_frame(&frame)?;
+        *written += delay;
+
+        Ok(())
+    }
+}
+
+fn centiseconds(time: Duration) -> u64 {
+    (time.as_millis() as u64 + 5) / 10
+}
+
+// the palette index of each pixel, `scale` times larger
+fn indices(framebuffer: &Framebuffer, scale: usize) -> Vec<u8> {
+    let mut indices = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * scale * scale);
+    for y in 0..SCREEN_HEIGHT * scale {
+        for x in 0..SCREEN_WIDTH * scale {
+            indices.push(framebuffer.pixel(x / scale, y / scale) as u8);
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn any_framebuffer(x: usize) -> Framebuffer {
+        let mut framebuffer = Framebuffer::default();
+        framebuffer.draw_sprite(&[0x80], x, 0, false);
+        framebuffer
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("chip8-rs-{}-{}", std::process::id(), name))
+    }
+
+    // the delay of each frame of a GIF file, in centiseconds
+    fn gif_delays(path: &Path) -> Vec<u16> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(File::open(path).unwrap()).unwrap();
+
+        let mut delays = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        delays
+    }
+
+    #[test]
+    fn test_indices() {
+        let indices = indices(&any_framebuffer(1), 2);
+
+        assert_eq!(indices.len(), 128 * 64);
+        assert_eq!(indices[..4], [0, 0, 1, 1]);
+        assert_eq!(indices[128..132], [0, 0, 1, 1]);
+        assert_eq!(indices[256..260], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_gif_keeps_only_changes_with_their_delays() {
+        let path = temp_path("changes.gif");
+        let mut recorder = VideoRecorder::create(&path, &Palette::default(), 1).unwrap();
+
+        recorder.push(&any_framebuffer(0), ms(0)).unwrap();
+        recorder.push(&any_framebuffer(0), ms(100)).unwrap();
+        recorder.push(&any_framebuffer(1), ms(250)).unwrap();
+        recorder.push(&any_framebuffer(2), ms(300)).unwrap();
+        recorder.finish(ms(1000)).unwrap();
+
+        let delays = gif_delays(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(delays, [25, 5, 70]);
+    }
+
+    #[test]
+    fn test_gif_drops_frames_too_short_to_show() {
+        let path = temp_path("short.gif");
+        let mut recorder = VideoRecorder::create(&path, &Palette::default(), 1).unwrap();
+
+        recorder.push(&any_framebuffer(0), ms(0)).unwrap();
+        recorder.push(&any_framebuffer(1), ms(100)).unwrap();
+        recorder.push(&any_framebuffer(2), ms(105)).unwrap();
+        recorder.finish(ms(200)).unwrap();
+
+        let delays = gif_delays(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(delays, [10, 10]);
+    }
+
+    #[test]
+    fn test_images_are_named_after_their_frame() {
+        let path = temp_path("frames");
+        let mut recorder = VideoRecorder::create(&path, &Palette::default(), 1).unwrap();
+
+        recorder.push(&any_framebuffer(0), ms(0)).unwrap();
+        recorder.push(&any_framebuffer(0), ms(500)).unwrap();
+        recorder.push(&any_framebuffer(1), ms(1000)).unwrap();
+        recorder.finish(ms(2000)).unwrap();
+
+        let mut names: Vec<String> = fs::read_dir(&path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        fs::remove_dir_all(&path).unwrap();
+        assert_eq!(names, ["000000.png", "000060.png"]);
+    }
+}
@@ -0,0 +1,387 @@
+use std::time::{Duration, Instant};
+
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+
+use crate::cpu::{Snapshot, MEM_SIZE};
+use crate::error::{to_sdl_err, Error};
+use crate::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+// text is drawn this many times larger
+const SCALE: u32 = 2;
+// in font pixels
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+const CHAR_WIDTH: u32 = GLYPH_WIDTH + 1;
+const MARGIN: u32 = 2;
+const BYTES_PER_ROW: usize = 16;
+// rows of the memory view shown at once
+const VISIBLE_ROWS: usize = 32;
+// rows scrolled by a turn of the mouse wheel
+const WHEEL_ROWS: i32 = 3;
+// characters before the first byte of a row, i.e. "200  "
+const ADDRESS_COLUMNS: u32 = 5;
+// how long changed bytes flash for
+const FLASH_DURATION: Duration = Duration::from_millis(500);
+// bytes shown in the sprite view, one per row
+const SPRITE_ROWS: usize = 32;
+// characters before the pixels of a sprite row, i.e. "300 F0 "
+const SPRITE_COLUMNS: u32 = 7;
+const BACKGROUND_COLOR: Color = Color::RGB(16, 16, 16);
+const TEXT_COLOR: Color = Color::RGB(200, 200, 200);
+const PC_COLOR: Color = Color::RGB(160, 130, 0);
+const I_COLOR: Color = Color::RGB(0, 110, 160);
+const FLASH_COLOR: Color = Color::RGB(220, 40, 40);
+const PIXEL_OFF_COLOR: Color = Color::RGB(40, 40, 40);
+const PIXEL_ON_COLOR: Color = Color::RGB(230, 230, 230);
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A window with a scrolling hex view of the memory, where the bytes PC and
+/// I point at are highlighted, and the bytes that change flash for a while.
+pub struct MemoryViewer {
+    canvas: Canvas<Window>,
+    // the first row shown
+    scroll: usize,
+    changes: Changes,
+    // as of the last frame, to scroll to
+    pc: u16,
+    i_register: u16,
+}
+
+impl MemoryViewer {
+    pub fn open(video: &VideoSubsystem) -> Result<Self> {
+        let width = (ADDRESS_COLUMNS + BYTES_PER_ROW as u32 * 3) * CHAR_WIDTH;
+        let height = VISIBLE_ROWS as u32 * LINE_HEIGHT;
+        let canvas = open_window(video, "CHIP-8 memory", (width, height))?;
+
+        Ok(Self {
+            canvas,
+            scroll: 0,
+            changes: Changes::default(),
+            pc: 0,
+            i_register: 0,
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    /// Scrolls with the mouse wheel, the arrow and page keys, `Home` and
+    /// `End`, or to the row of PC or I with `P` and `I`. Returns whether the
+    /// window should be closed.
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        if is_close_event(event) {
+            return true;
+        }
+
+        let pages = VISIBLE_ROWS as i32;
+        match event {
+            Event::MouseWheel { y, .. } => self.scroll_by(-y * WHEEL_ROWS),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match keycode {
+                Keycode::Up => self.scroll_by(-1),
+                Keycode::Down => self.scroll_by(1),
+                Keycode::PageUp => self.scroll_by(-pages),
+                Keycode::PageDown => self.scroll_by(pages),
+                Keycode::Home => self.scroll = 0,
+                Keycode::End => self.scroll = max_scroll(),
+                Keycode::P => self.scroll_to(self.pc),
+                Keycode::I => self.scroll_to(self.i_register),
+                _ => {}
+            },
+            _ => {}
+        }
+
+        false
+    }
+
+    fn scroll_by(&mut self, rows: i32) {
+        self.scroll = (self.scroll as i32 + rows).clamp(0, max_scroll() as i32) as usize;
+    }
+
+    // shows an address a few rows from the top
+    fn scroll_to(&mut self, address: u16) {
+        let row = address as usize / BYTES_PER_ROW;
+        self.scroll = row.saturating_sub(2).min(max_scroll());
+    }
+
+    pub fn draw(&mut self, machine: &Snapshot, now: Instant) -> Result<()> {
+        self.changes.update(&machine.memory[..], now);
+        self.pc = machine.pc;
+        self.i_register = machine.i_register;
+
+        let canvas = &mut self.canvas;
+        canvas.set_draw_color(BACKGROUND_COLOR);
+        canvas.clear();
+
+        let rows = self.scroll..(self.scroll + VISIBLE_ROWS).min(row_count());
+        for (line, row) in rows.enumerate() {
+            let y = ((MARGIN + line as u32 * LINE_HEIGHT) * SCALE) as i32;
+
+            for column in 0..BYTES_PER_ROW {
+                let address = row * BYTES_PER_ROW + column;
+                let x = (MARGIN + (ADDRESS_COLUMNS + column as u32 * 3) * CHAR_WIDTH) * SCALE;
+                // around the two digits of the byte
+                let cell = Rect::new(
+                    x as i32 - SCALE as i32,
+                    y - SCALE as i32,
+                    (2 * CHAR_WIDTH + 1) * SCALE,
+                    LINE_HEIGHT * SCALE,
+                );
+
+                let flash = self.changes.flash(address, now);
+                if flash > 0.0 {
+                    canvas.set_blend_mode(BlendMode::Blend);
+                    let alpha = (flash * 255.0) as u8;
+                    canvas.set_draw_color(Color::RGBA(
+                        FLASH_COLOR.r,
+                        FLASH_COLOR.g,
+                        FLASH_COLOR.b,
+                        alpha,
+                    ));
+                    canvas.fill_rect(cell).map_err(to_sdl_err)?;
+                    canvas.set_blend_mode(BlendMode::None);
+                } else if is_opcode_byte(address, machine.pc) {
+                    canvas.set_draw_color(PC_COLOR);
+                    canvas.fill_rect(cell).map_err(to_sdl_err)?;
+                } else if address == machine.i_register as usize {
+                    canvas.set_draw_color(I_COLOR);
+                    canvas.fill_rect(cell).map_err(to_sdl_err)?;
+                }
+            }
+
+            let x = (MARGIN * SCALE) as i32;
+            let text = memory_line(&machine.memory[..], row);
+            font::draw_text(canvas, &text, (x, y), SCALE, TEXT_COLOR)?;
+        }
+        canvas.present();
+
+        Ok(())
+    }
+}
+
+/// A window showing the memory from I as 8-pixel-wide sprite rows, one byte
+/// per row, to find graphics in memory.
+pub struct SpriteViewer {
+    canvas: Canvas<Window>,
+}
+
+impl SpriteViewer {
+    pub fn open(video: &VideoSubsystem) -> Result<Self> {
+        let width = SPRITE_COLUMNS * CHAR_WIDTH + 8 * LINE_HEIGHT;
+        let height = SPRITE_ROWS as u32 * LINE_HEIGHT;
+        let canvas = open_window(video, "CHIP-8 sprites", (width, height))?;
+
+        Ok(Self { canvas })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    /// Returns whether the window should be closed
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        is_close_event(event)
+    }
+
+    pub fn draw(&mut self, machine: &Snapshot) -> Result<()> {
+        let canvas = &mut self.canvas;
+        canvas.set_draw_color(BACKGROUND_COLOR);
+        canvas.clear();
+
+        let cell = LINE_HEIGHT * SCALE;
+        let left = ((MARGIN + SPRITE_COLUMNS * CHAR_WIDTH) * SCALE) as i32;
+        let rows = sprite_rows(&machine.memory[..], machine.i_register, SPRITE_ROWS);
+        for (line, (address, byte)) in rows.into_iter().enumerate() {
+            let y = ((MARGIN + line as u32 * LINE_HEIGHT) * SCALE) as i32;
+            let text = format!("{:03X} {:02X}", address, byte);
+            font::draw_text(
+                canvas,
+                &text,
+                ((MARGIN * SCALE) as i32, y),
+                SCALE,
+                TEXT_COLOR,
+            )?;
+
+            for bit in 0..8 {
+                let is_on = byte & (0x80 >> bit) != 0;
+                canvas.set_draw_color(if is_on {
+                    PIXEL_ON_COLOR
+                } else {
+                    PIXEL_OFF_COLOR
+                });
+                // a pixel apart, so the rows can be told apart
+                let pixel = Rect::new(
+                    left + (bit * cell) as i32,
+                    y - SCALE as i32,
+                    cell - SCALE,
+                    cell - SCALE,
+                );
+                canvas.fill_rect(pixel).map_err(to_sdl_err)?;
+            }
+        }
+        canvas.present();
+
+        Ok(())
+    }
+}
+
+/// When each byte of memory last changed, from one frame to the next
+#[derive(Debug, Default)]
+struct Changes {
+    previous: Vec<u8>,
+    changed_at: Vec<Option<Instant>>,
+}
+
+impl Changes {
+    fn update(&mut self, memory: &[u8], now: Instant) {
+        if self.previous.len() == memory.len() {
+            let bytes = self.previous.iter().zip(memory);
+            for ((previous, byte), changed_at) in bytes.zip(&mut self.changed_at) {
+                if previous != byte {
+                    *changed_at = Some(now);
+                }
+            }
+        } else {
+            self.changed_at = vec![None; memory.len()];
+        }
+        self.previous = memory.to_vec();
+    }
+
+    // how bright the flash of a byte is at `now`, from 1.0 as it changed
+    // down to 0.0
+    fn flash(&self, address: usize, now: Instant) -> f32 {
+        match self.changed_at.get(address) {
+            Some(Some(changed_at)) => {
+                let elapsed = now.duration_since(*changed_at);
+                1.0 - (elapsed.as_secs_f32() / FLASH_DURATION.as_secs_f32()).min(1.0)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+fn open_window(
+    video: &VideoSubsystem,
+    title: &str,
+    (width, height): (u32, u32),
+) -> Result<Canvas<Window>> {
+    let size = ((width + 2 * MARGIN) * SCALE, (height + 2 * MARGIN) * SCALE);
+    let window = video.window(title, size.0, size.1).build()?;
+    // without vsync, so drawing it doesn't hold back the main window
+    Ok(window.into_canvas().build()?)
+}
+
+fn is_close_event(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Window {
+            win_event: WindowEvent::Close,
+            ..
+        } | Event::KeyDown {
+            keycode: Some(Keycode::Escape),
+            ..
+        }
+    )
+}
+
+fn row_count() -> usize {
+    MEM_SIZE / BYTES_PER_ROW
+}
+
+fn max_scroll() -> usize {
+    row_count() - VISIBLE_ROWS
+}
+
+// whether an address holds a byte of the instruction at PC
+fn is_opcode_byte(address: usize, pc: u16) -> bool {
+    address == pc as usize || address == pc as usize + 1
+}
+
+/// A row of the memory view: its address, and its bytes in hex
+fn memory_line(memory: &[u8], row: usize) -> String {
+    let start = row * BYTES_PER_ROW;
+    let bytes: Vec<String> = memory[start..start + BYTES_PER_ROW]
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    format!("{:03X}  {}", start, bytes.join(" "))
+}
+
+/// The bytes from `start`, with their addresses, up to `count` or the end of
+/// the memory
+fn sprite_rows(memory: &[u8], start: u16, count: usize) -> Vec<(usize, u8)> {
+    let start = start as usize;
+    memory
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(count)
+        .map(|(address, byte)| (address, *byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_line() {
+        let mut memory = vec![0; 4096];
+        memory[0x210] = 0xA2;
+        memory[0x21F] = 0x0F;
+
+        let line = memory_line(&memory, 0x21);
+
+        assert_eq!(line, "210  A2 00 00 00 00 00 00 00 00 00 00 00 00 00 00 0F");
+        assert_eq!(
+            line.len() as u32,
+            ADDRESS_COLUMNS + BYTES_PER_ROW as u32 * 3 - 1
+        );
+    }
+
+    #[test]
+    fn test_sprite_rows_stop_at_the_end_of_memory() {
+        let mut memory = vec![0; 4096];
+        memory[0xFFF] = 0x3C;
+
+        assert_eq!(
+            sprite_rows(&memory, 0xFFE, 4),
+            [(0xFFE, 0x00), (0xFFF, 0x3C)]
+        );
+        assert_eq!(sprite_rows(&memory, 0x300, 4).len(), 4);
+    }
+
+    #[test]
+    fn test_changes_flash_the_bytes_that_changed() {
+        let start = Instant::now();
+        let mut changes = Changes::default();
+        let mut memory = vec![0; 16];
+        changes.update(&memory, start);
+        assert_eq!(changes.flash(3, start), 0.0);
+
+        memory[3] = 0xFF;
+        changes.update(&memory, start);
+        changes.update(&memory, start + FLASH_DURATION / 2);
+
+        assert_eq!(changes.flash(3, start), 1.0);
+        assert_eq!(changes.flash(3, start + FLASH_DURATION / 2), 0.5);
+        assert_eq!(changes.flash(3, start + FLASH_DURATION), 0.0);
+        assert_eq!(changes.flash(4, start), 0.0);
+    }
+
+    #[test]
+    fn test_is_opcode_byte() {
+        assert!(is_opcode_byte(0x200, 0x200));
+        assert!(is_opcode_byte(0x201, 0x200));
+        assert!(!is_opcode_byte(0x202, 0x200));
+    }
+}
@@ -1,111 +1,712 @@
 use rand::RngCore;
 use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use sdl2::event::Event;
-use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::{FullscreenType, Window};
 use sdl2::EventPump;
 
-use crate::audio::Audio;
-use crate::cpu::CPU;
-use crate::error::Error;
-use crate::screen;
+use crate::audio::{Audio, HeadlessRecorder};
+use crate::clock::{Clock, Stats};
+use crate::config::{Config, TIMER_HZ};
+use crate::cpu::{KeyWait, Snapshot, CPU, MAX_ROM_SIZE};
+use crate::database;
+use crate::emulation::{self, Command, Emulation};
+use crate::error::{to_sdl_err, CPUError, Error};
+use crate::framebuffer::Framebuffer;
+use crate::gamepad::Gamepads;
+use crate::input::Input;
+use crate::instruction::Key;
+use crate::keymap::Keymap;
+use crate::osd::Osd;
+use crate::palette::Palette;
+use crate::panel;
+use crate::quirks::Quirks;
+use crate::screen::{self, Screen};
+use crate::screenshot::Image;
+use crate::settings::{self, Location, Settings, WindowSize};
+use crate::video::VideoRecorder;
+use crate::viewer::{MemoryViewer, SpriteViewer};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-const FPS: f64 = 60.0;
-const FRAMES_PER_CYCLE: i32 = 1;
+const DEFAULT_KEYMAP: &str = "qwerty";
 
-pub struct VM<'a> {
-    cpu: CPU<'a>,
-    keymap: HashMap<Scancode, u8>,
+pub struct VM {
+    cpu: CPU,
+    frontend: Frontend,
+    // settings given by the user for this run, and the global defaults
+    overrides: Settings,
+    defaults: Settings,
+    // path and SHA-1 hash of the loaded ROM
+    rom: Option<(PathBuf, String)>,
+    config: Config,
+    // where to record the buzzer to, from the start of the run
+    audio_recording: Option<PathBuf>,
+    headless_recorder: Option<HeadlessRecorder<BufWriter<File>>>,
+    // keeps the pace of headless runs across calls
+    headless_clock: Clock,
+    // where to record the screen to, and how many times larger, from the
+    // start of the run
+    video_recording: Option<(PathBuf, u32)>,
+    headless_video: Option<VideoRecorder>,
+    // timer ticks run headless since the video recording started
+    headless_ticks: u32,
 }
 
-impl<'a> VM<'a> {
-    pub fn new(rng: &'a mut impl RngCore) -> Self {
+// the state of the window side of the emulator, kept apart from the CPU so
+// the CPU can run on another thread
+struct Frontend {
+    input: Input,
+    // palettes to switch between at runtime, and the one in use
+    palettes: Vec<Palette>,
+    palette_index: usize,
+    // reloaded on reset, and names recordings made with hotkeys
+    rom_path: PathBuf,
+    // the screen recording in progress, and when it started
+    video: Option<(VideoRecorder, Instant)>,
+    is_paused: bool,
+    is_fast_forward: bool,
+    is_muted: bool,
+    // how many times faster to run while fast-forwarding
+    fast_forward: u32,
+    osd: Osd,
+    // the debug panel, beside the screen
+    is_panel_shown: bool,
+    // debug windows, when open
+    memory_viewer: Option<MemoryViewer>,
+    sprite_viewer: Option<SpriteViewer>,
+}
+
+impl VM {
+    pub fn new(
+        rng: impl RngCore + Send + 'static,
+        overrides: Settings,
+        defaults: Settings,
+    ) -> Self {
         Self {
             cpu: CPU::new(rng),
-            keymap: Self::default_keymap(),
+            frontend: Frontend {
+                input: Input::new(Keymap::default()),
+                // until a ROM picks its palette
+                palettes: vec![Palette::default()],
+                palette_index: 0,
+                rom_path: PathBuf::new(),
+                video: None,
+                is_paused: false,
+                is_fast_forward: false,
+                is_muted: false,
+                fast_forward: Config::default().fast_forward,
+                osd: Osd::default(),
+                is_panel_shown: false,
+                memory_viewer: None,
+                sprite_viewer: None,
+            },
+            overrides,
+            defaults,
+            rom: None,
+            config: Config::default(),
+            audio_recording: None,
+            headless_recorder: None,
+            headless_clock: Clock::new(Config::default().ips),
+            video_recording: None,
+            headless_video: None,
+            headless_ticks: 0,
         }
     }
 
+    /// Loads a ROM, applying its settings (quirks, tickrate, keys, colors...).
+    /// See [`Settings`] for the order they are looked up in.
     pub fn load_rom(&mut self, filename: PathBuf) -> Result<()> {
         self.reset();
 
-        let rom = fs::read(filename)?;
+        let rom = fs::read(&filename)?;
+        let hash = database::sha1(&rom);
+        let known = database::lookup_hash(&hash)
+            .map(|info| Settings::from(&info))
+            .unwrap_or_default();
+
+        let settings = self
+            .overrides
+            .clone()
+            .or(Settings::load_for_rom(&filename, &hash)?)
+            .or(known)
+            .or(self.defaults.clone());
+        self.apply_settings(&settings)?;
+
         self.cpu.load_rom(&rom)?;
+        self.frontend.rom_path = filename.clone();
+        self.rom = Some((filename, hash));
 
         Ok(())
     }
 
+    /// Saves the settings given by the user for the loaded ROM, on top of the
+    /// ones already saved there. Returns the path of the settings file.
+    pub fn save_settings(&self, location: Location) -> Result<PathBuf> {
+        self.save_rom_settings(location, self.overrides.clone())
+    }
+
+    // saves the palette picked while running, in the sidecar file if the ROM
+    // has one
+    fn save_palette(&self) -> Result<PathBuf> {
+        let location = match &self.rom {
+            Some((filename, _)) if settings::sidecar_path(filename).exists() => Location::Sidecar,
+            _ => Location::Config,
+        };
+        let settings = Settings {
+            palette: Some(self.frontend.palette().setting()),
+            ..Settings::default()
+        };
+
+        self.save_rom_settings(location, settings)
+    }
+
+    fn save_rom_settings(&self, location: Location, settings: Settings) -> Result<PathBuf> {
+        let (filename, hash) = self
+            .rom
+            .as_ref()
+            .ok_or_else(|| Error::ConfigError("No ROM loaded".to_string()))?;
+
+        let path = match location {
+            Location::Sidecar => settings::sidecar_path(filename),
+            Location::Config => settings::rom_config_path(hash).ok_or_else(|| {
+                Error::ConfigError("Could not find the config directory".to_string())
+            })?,
+        };
+
+        let saved = Settings::load(&path)?.unwrap_or_default();
+        settings.or(saved).save(&path)?;
+
+        Ok(path)
+    }
+
+    /// Records the buzzer to a WAV file from the start of the run, with the
+    /// window or headless
+    pub fn record_audio(&mut self, path: PathBuf) {
+        self.audio_recording = Some(path);
+    }
+
+    /// Records the screen to a GIF file, or to a directory of PNG files, from
+    /// the start of the run, with the window or headless
+    pub fn record_video(&mut self, path: PathBuf, scale: u32) {
+        self.video_recording = Some((path, scale));
+    }
+
     pub fn run(&mut self) -> Result<()> {
         let sdl_context = sdl2::init().map_err(to_sdl_err)?;
-        let (mut canvas, texture_creator) = screen::build_canvas_and_creator(&sdl_context)?;
-        let mut screen = screen::Screen::try_from(&texture_creator)?;
+        let (mut canvas, texture_creator) =
+            screen::build_canvas_and_creator(&sdl_context, &self.config)?;
+        let mut screen = Screen::new(&texture_creator, &self.config)?;
         let mut event_pump = sdl_context.event_pump().map_err(to_sdl_err)?;
 
-        let mut buzzer = Audio::new(&sdl_context, 1.0)?;
+        let mut buzzer = Audio::new(&sdl_context, &self.config)?;
+        if let Some(path) = self.audio_recording.clone() {
+            buzzer.start_recording(path)?;
+        }
+        if let Some((path, scale)) = &self.video_recording {
+            self.frontend.start_video_recording(path.clone(), *scale)?;
+        }
+        let mut gamepads = Gamepads::new(&sdl_context, self.config.rumble)?;
+
+        let Self {
+            cpu,
+            frontend,
+            config,
+            ..
+        } = self;
+        let frame_duration = Duration::from_secs_f64(1.0 / config.fps);
+        let mut stats = Stats::new(Instant::now());
+        let mut has_stats = false;
+        let mut title = String::new();
+        let mut next_frame = Instant::now();
+        // whether the frames carry a snapshot for the debug views
+        let mut is_debugging = false;
+
+        thread::scope(|scope| -> Result<()> {
+            let mut emulation = if config.threaded {
+                Emulation::spawn(scope, cpu, config.ips)
+            } else {
+                Emulation::local(cpu, config.ips)
+            };
 
-        let mut frames = -1;
+            loop {
+                let shall_halt = frontend.handle_user_input(
+                    &mut event_pump,
+                    &mut canvas,
+                    &mut screen,
+                    &mut gamepads,
+                    &mut buzzer,
+                    &mut emulation,
+                )?;
+                if shall_halt {
+                    break;
+                }
+                if frontend.has_debug_view() != is_debugging {
+                    is_debugging = !is_debugging;
+                    emulation.send(Command::Debug(is_debugging))?;
+                }
 
-        loop {
-            let shall_halt = self.handle_user_input(&mut event_pump)?;
-            if shall_halt {
-                break;
+                let frame = emulation.frame()?;
+                // the sound timer stands still while paused
+                let is_buzzing = frame.is_buzzing && !frontend.is_paused;
+                buzzer.buzz(frame.progress.sound_ticks, is_buzzing);
+                gamepads.set_buzzing(is_buzzing);
+
+                stats.record_frame(frame.progress.cycles);
+                if stats.update(Instant::now()) {
+                    has_stats = true;
+                    let counter = format!("{:.0} FPS {:.0} IPS", stats.fps, stats.ips);
+                    frontend.osd.set_counter(config.show_fps.then_some(counter));
+                }
+                frontend.osd.set_status(frontend.status());
+
+                let (width, height) = canvas.output_size().map_err(to_sdl_err)?;
+                let panel_width = if frontend.is_panel_shown {
+                    panel::width(height).min(width / 2)
+                } else {
+                    0
+                };
+                let viewport = screen.frame(
+                    &mut canvas,
+                    &frame.framebuffer,
+                    frame.screen_generation,
+                    Rect::new(0, 0, width - panel_width, height),
+                )?;
+                if let (true, Some(machine)) = (panel_width > 0, &frame.machine) {
+                    let area = Rect::new((width - panel_width) as i32, 0, panel_width, height);
+                    panel::draw(&mut canvas, area, machine)?;
+                }
+                frontend.osd.draw(&mut canvas, viewport, Instant::now())?;
+                canvas.present();
+                if let Some(machine) = &frame.machine {
+                    frontend.draw_viewers(machine)?;
+                }
+                frontend.record_frame(&frame.framebuffer)?;
+
+                let new_title = frontend.title(has_stats.then_some(&stats));
+                if new_title != title {
+                    // the title is only informative, so errors are ignored
+                    let _ = canvas.window_mut().set_title(&new_title);
+                    title = new_title;
+                }
+
+                // wait for the next frame, or start over if running late
+                next_frame += frame_duration;
+                let now = Instant::now();
+                if next_frame > now {
+                    thread::sleep(next_frame - now);
+                } else if now - next_frame > frame_duration {
+                    next_frame = now;
+                }
+            }
+
+            Ok(())
+        })?;
+
+        if let Some(path) = buzzer.stop_recording()? {
+            println!("Audio saved to {}", path.display());
+        }
+        self.frontend.stop_video_recording()?;
+        self.frontend.memory_viewer = None;
+        self.frontend.sprite_viewer = None;
+
+        let window = canvas.window();
+        if window.fullscreen_state() == FullscreenType::Off {
+            let (width, height) = window.size();
+            if let Err(err) = (WindowSize { width, height }).save() {
+                eprintln!("Could not save the window size: {}", err);
             }
+        }
+
+        if *self.frontend.palette() != self.config.palette {
+            match self.save_palette() {
+                Ok(path) => println!("Palette saved to {}", path.display()),
+                Err(err) => eprintln!("Could not save the palette: {}", err),
+            }
+        }
+
+        Ok(())
+    }
 
-            frames += 1;
+    /// Runs the loaded ROM for a number of 60 Hz timer ticks without a
+    /// window, as fast as possible
+    pub fn run_headless(&mut self, ticks: u32) -> Result<()> {
+        let clock = &mut self.headless_clock;
 
-            if frames % FRAMES_PER_CYCLE == 0 {
-                let status = self.cpu.tick()?;
-                buzzer.set_status(status.is_buzzing);
-                frames = 0;
+        if self.headless_recorder.is_none() {
+            if let Some(path) = &self.audio_recording {
+                self.headless_recorder = Some(HeadlessRecorder::create(path, &self.config)?);
+            }
+        }
+        if self.headless_video.is_none() {
+            if let Some((path, scale)) = &self.video_recording {
+                let recorder = VideoRecorder::create(path, self.frontend.palette(), *scale)?;
+                self.headless_video = Some(recorder);
+                self.headless_ticks = 0;
             }
+        }
 
-            screen.frame(&mut canvas, self.cpu.visual_buffer())?;
+        if self.headless_recorder.is_none() && self.headless_video.is_none() {
+            emulation::run_timer_ticks(&mut self.cpu, clock, ticks)?;
+            return Ok(());
+        }
 
-            ::std::thread::sleep(std::time::Duration::from_millis(
-                (1.0 / FPS * 1000.0) as u64,
-            ));
+        for _ in 0..ticks {
+            let progress = emulation::run_timer_ticks(&mut self.cpu, clock, 1)?;
+            if let Some(recorder) = &mut self.headless_recorder {
+                recorder.record_tick(progress.sound_ticks > 0)?;
+            }
+            if let Some(video) = &mut self.headless_video {
+                video.push(
+                    self.cpu.visual_buffer(),
+                    ticks_duration(self.headless_ticks),
+                )?;
+            }
+            self.headless_ticks += 1;
         }
 
         Ok(())
     }
 
-    fn default_keymap() -> HashMap<Scancode, u8> {
-        HashMap::from([
-            (Scancode::Num1, 0x01),
-            (Scancode::Num2, 0x02),
-            (Scancode::Num3, 0x03),
-            (Scancode::Num4, 0x0C),
-            (Scancode::Q, 0x04),
-            (Scancode::W, 0x05),
-            (Scancode::E, 0x06),
-            (Scancode::R, 0x0D),
-            (Scancode::A, 0x07),
-            (Scancode::S, 0x08),
-            (Scancode::D, 0x09),
-            (Scancode::F, 0x0E),
-            (Scancode::Z, 0x0A),
-            (Scancode::X, 0x00),
-            (Scancode::C, 0x0B),
-            (Scancode::V, 0x0F),
-            (Scancode::Left, 0x07),
-            (Scancode::Right, 0x09),
-            (Scancode::Up, 0x05),
-            (Scancode::Down, 0x08),
-        ])
+    /// Closes the recordings of headless runs
+    pub fn finish_headless(&mut self) -> Result<()> {
+        if let Some(recorder) = self.headless_recorder.take() {
+            recorder.finish()?;
+        }
+        if let Some(video) = self.headless_video.take() {
+            video.finish(ticks_duration(self.headless_ticks))?;
+        }
+
+        Ok(())
+    }
+
+    /// Presses or releases a CHIP-8 key, for headless runs
+    pub fn set_key(&mut self, key: u8, is_pressed: bool) -> Result<()> {
+        self.cpu.set_key_status(Key::try_from(key)?, is_pressed);
+        Ok(())
+    }
+
+    /// Saves the screen as a PNG or PPM file (by its extension), in the
+    /// colors of the palette in use, `scale` times larger
+    pub fn screenshot(&self, path: &Path, scale: u32) -> Result<()> {
+        Image::new(self.cpu.visual_buffer(), self.frontend.palette(), scale).save(path)
+    }
+
+    fn apply_settings(&mut self, settings: &Settings) -> Result<()> {
+        self.cpu
+            .set_quirks(Quirks::default().with_overrides(&settings.quirks));
+
+        let key_wait = if settings.wait_key_release.unwrap_or(false) {
+            KeyWait::Release
+        } else {
+            KeyWait::Press
+        };
+        self.cpu.set_key_wait(key_wait);
+
+        let keymap = Keymap::load(settings.keymap.as_deref().unwrap_or(DEFAULT_KEYMAP))?;
+        let actions: HashMap<String, u8> = settings.actions.clone().into_iter().collect();
+        self.frontend.input = Input::new(keymap.with_actions(&actions));
+
+        self.config = Config::try_from(settings)?;
+        self.headless_clock = Clock::new(self.config.ips);
+        self.frontend.fast_forward = self.config.fast_forward;
+        if settings.scale.is_none() {
+            match WindowSize::load() {
+                Ok(Some(size)) => self.config.window_size = (size.width, size.height),
+                Ok(None) => {}
+                Err(err) => eprintln!("Could not load the window size: {}", err),
+            }
+        }
+
+        let frontend = &mut self.frontend;
+        frontend.palettes = Palette::all(&settings.palettes)?;
+        frontend.palette_index = match frontend
+            .palettes
+            .iter()
+            .position(|palette| *palette == self.config.palette)
+        {
+            Some(index) => index,
+            None => {
+                frontend.palettes.insert(0, self.config.palette.clone());
+                0
+            }
+        };
+
+        Ok(())
     }
 
     fn reset(&mut self) {
         self.cpu.reset();
     }
+}
+
+impl Frontend {
+    /// Switches to the next palette (or the previous one, going backwards)
+    fn cycle_palette(&mut self, screen: &mut Screen, backwards: bool) {
+        let count = self.palettes.len();
+        self.palette_index = if backwards {
+            (self.palette_index + count - 1) % count
+        } else {
+            (self.palette_index + 1) % count
+        };
+
+        screen.set_palette(self.palette().clone());
+        self.notify(format!("Palette {}", self.palette().name));
+    }
+
+    // prints a message, and shows it over the screen for a while
+    fn notify(&mut self, message: String) {
+        println!("{}", message);
+        self.osd.show(message, Instant::now());
+    }
+
+    fn palette(&self) -> &Palette {
+        &self.palettes[self.palette_index]
+    }
+
+    fn save_screenshot(&mut self, emulation: &Emulation) -> Result<()> {
+        let path = self.capture_path("png");
+        Image::new(
+            &emulation.framebuffer(),
+            self.palette(),
+            screen::DEFAULT_SCALE,
+        )
+        .save(&path)?;
+        self.notify(format!("Screenshot saved to {}", path.display()));
+
+        Ok(())
+    }
+
+    fn toggle_fullscreen(canvas: &mut Canvas<Window>) -> Result<()> {
+        let window = canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        window.set_fullscreen(fullscreen).map_err(to_sdl_err)
+    }
+
+    /// Starts recording the buzzer, or stops the recording in progress
+    fn toggle_audio_recording(&mut self, buzzer: &mut Audio) -> Result<()> {
+        if buzzer.is_recording() {
+            if let Some(path) = buzzer.stop_recording()? {
+                self.notify(format!("Audio saved to {}", path.display()));
+            }
+        } else {
+            let path = self.capture_path("wav");
+            buzzer.start_recording(path.clone())?;
+            self.notify(format!("Recording audio to {}", path.display()));
+        }
+
+        Ok(())
+    }
+
+    fn start_video_recording(&mut self, path: PathBuf, scale: u32) -> Result<()> {
+        let recorder = VideoRecorder::create(&path, self.palette(), scale)?;
+        self.notify(format!("Recording video to {}", path.display()));
+        self.video = Some((recorder, Instant::now()));
+
+        Ok(())
+    }
+
+    fn stop_video_recording(&mut self) -> Result<()> {
+        if let Some((recorder, start)) = self.video.take() {
+            let path = recorder.finish(start.elapsed())?;
+            self.notify(format!("Video saved to {}", path.display()));
+        }
+
+        Ok(())
+    }
+
+    fn toggle_video_recording(&mut self) -> Result<()> {
+        if self.video.is_some() {
+            self.stop_video_recording()
+        } else {
+            self.start_video_recording(self.capture_path("gif"), screen::DEFAULT_SCALE)
+        }
+    }
+
+    fn record_frame(&mut self, framebuffer: &Framebuffer) -> Result<()> {
+        if let Some((recorder, start)) = &mut self.video {
+            recorder.push(framebuffer, start.elapsed())?;
+        }
+
+        Ok(())
+    }
+
+    // a new file in the working directory, named after the ROM and the time
+    fn capture_path(&self, extension: &str) -> PathBuf {
+        let name = self
+            .rom_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or_default();
+        PathBuf::from(format!("{}-{}.{}", name, millis, extension))
+    }
+
+    /// The window title, with the frame and instruction rates once known,
+    /// and what the emulation is doing
+    fn title(&self, stats: Option<&Stats>) -> String {
+        let mut title = screen::WINDOW_TITLE.to_string();
+        if let Some(stats) = stats {
+            title += &format!(" - {:.0} FPS, {:.0} IPS", stats.fps, stats.ips);
+        }
+        if self.is_paused {
+            title += " - Paused";
+        } else if self.is_fast_forward {
+            title += &format!(" - Fast-forward {}x", self.fast_forward);
+        }
+        if self.is_muted {
+            title += " - Muted";
+        }
+
+        title
+    }
+
+    /// What the emulation is doing, when it's not just running
+    fn status(&self) -> Option<String> {
+        if self.is_paused {
+            Some("Paused".to_string())
+        } else if self.is_fast_forward {
+            Some(format!("Speed {}%", self.fast_forward * 100))
+        } else {
+            None
+        }
+    }
+
+    // letter hotkeys give way to the keys of the keymap
+    fn is_hotkey(&self, scancode: Option<Scancode>) -> bool {
+        scancode.is_none_or(|code| !self.input.is_bound(code))
+    }
+
+    fn toggle_pause(&mut self, emulation: &mut Emulation) -> Result<()> {
+        self.is_paused = !self.is_paused;
+        emulation.send(Command::Pause(self.is_paused))
+    }
+
+    fn set_fast_forward(&mut self, is_fast_forward: bool, emulation: &mut Emulation) -> Result<()> {
+        self.is_fast_forward = is_fast_forward;
+        let speed = if is_fast_forward {
+            self.fast_forward
+        } else {
+            1
+        };
+        emulation.send(Command::Speed(speed))
+    }
+
+    /// Resets the CPU and loads the ROM again, keeping its settings. The
+    /// machine keeps running if the ROM can't be loaded anymore.
+    fn soft_reset(&mut self, emulation: &mut Emulation) -> Result<()> {
+        let rom = fs::read(&self.rom_path)?;
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(CPUError::MemoryOverflow.into());
+        }
+        emulation.send(Command::Reset(rom))?;
+        self.osd.show("Reset".to_string(), Instant::now());
+
+        Ok(())
+    }
+
+    fn toggle_mute(&mut self, buzzer: &mut Audio) {
+        self.is_muted = !self.is_muted;
+        buzzer.set_muted(self.is_muted);
+        let message = if self.is_muted { "Muted" } else { "Sound on" };
+        self.osd.show(message.to_string(), Instant::now());
+    }
+
+    /// Opens the memory viewer (or the sprite viewer), or closes it if open
+    fn toggle_viewer(&mut self, canvas: &Canvas<Window>, is_sprites: bool) -> Result<()> {
+        let video = canvas.window().subsystem();
+        if is_sprites {
+            self.sprite_viewer = match self.sprite_viewer {
+                Some(_) => None,
+                None => Some(SpriteViewer::open(video)?),
+            };
+        } else {
+            self.memory_viewer = match self.memory_viewer {
+                Some(_) => None,
+                None => Some(MemoryViewer::open(video)?),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Whether the panel or a debug window is open
+    fn has_debug_view(&self) -> bool {
+        self.is_panel_shown || self.memory_viewer.is_some() || self.sprite_viewer.is_some()
+    }
+
+    fn draw_viewers(&mut self, machine: &Snapshot) -> Result<()> {
+        if let Some(viewer) = &mut self.memory_viewer {
+            viewer.draw(machine, Instant::now())?;
+        }
+        if let Some(viewer) = &mut self.sprite_viewer {
+            viewer.draw(machine)?;
+        }
 
-    fn handle_user_input(&mut self, event_pump: &mut EventPump) -> Result<bool> {
+        Ok(())
+    }
+
+    // passes the events of the debug windows on to them, returning whether
+    // the event was theirs
+    fn handle_viewer_event(&mut self, event: &Event) -> bool {
+        let Some(window_id) = event.get_window_id() else {
+            return false;
+        };
+
+        if let Some(viewer) = &mut self.memory_viewer {
+            if viewer.window_id() == window_id {
+                if viewer.handle_event(event) {
+                    self.memory_viewer = None;
+                }
+                return true;
+            }
+        }
+        if let Some(viewer) = &mut self.sprite_viewer {
+            if viewer.window_id() == window_id {
+                if viewer.handle_event(event) {
+                    self.sprite_viewer = None;
+                }
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn handle_user_input(
+        &mut self,
+        event_pump: &mut EventPump,
+        canvas: &mut Canvas<Window>,
+        screen: &mut Screen,
+        gamepads: &mut Gamepads,
+        buzzer: &mut Audio,
+        emulation: &mut Emulation,
+    ) -> Result<bool> {
         for event in event_pump.poll_iter() {
+            if self.handle_viewer_event(&event) {
+                continue;
+            }
+
             match event {
                 Event::Quit { .. }
+                | Event::Window {
+                    win_event: WindowEvent::Close,
+                    ..
+                }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
@@ -113,19 +714,172 @@ impl<'a> VM<'a> {
                     return Ok(true);
                 }
                 Event::KeyDown {
-                    scancode: Some(ref code),
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => {
+                    Self::toggle_fullscreen(canvas)?;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+                    Self::toggle_fullscreen(canvas)?;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    keymod,
+                    ..
+                } => {
+                    self.cycle_palette(screen, keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD));
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
                     ..
                 } => {
-                    if let Some(key_index) = self.keymap.get(code) {
-                        self.cpu.set_key_status(*key_index as usize, true)?;
+                    if let Err(err) = self.save_screenshot(emulation) {
+                        self.notify(format!("Could not save the screenshot: {}", err));
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F8),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Err(err) = self.toggle_video_recording() {
+                        self.notify(format!("Could not record video: {}", err));
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    repeat: false,
+                    ..
+                } => {
+                    // a recording that fails doesn't end the session
+                    if let Err(err) = self.toggle_audio_recording(buzzer) {
+                        self.notify(format!("Could not record audio: {}", err));
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => {
+                    self.is_panel_shown = !self.is_panel_shown;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    self.toggle_viewer(canvas, keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD))?;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Err(err) = self.soft_reset(emulation) {
+                        self.notify(format!("Could not reset: {}", err));
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::P),
+                    scancode,
+                    repeat: false,
+                    ..
+                } if self.is_hotkey(scancode) => {
+                    self.toggle_pause(emulation)?;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::N),
+                    scancode,
+                    keymod,
+                    ..
+                } if self.is_paused && self.is_hotkey(scancode) => {
+                    let command = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        Command::Step
+                    } else {
+                        Command::AdvanceFrame
+                    };
+                    emulation.send(command)?;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    scancode,
+                    repeat: false,
+                    ..
+                } if self.is_hotkey(scancode) => {
+                    self.set_fast_forward(true, emulation)?;
+                }
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab),
+                    scancode,
+                    ..
+                } if self.is_hotkey(scancode) => {
+                    self.set_fast_forward(false, emulation)?;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    scancode,
+                    repeat: false,
+                    ..
+                } if self.is_hotkey(scancode) => {
+                    self.toggle_mute(buzzer);
+                }
+                Event::KeyDown {
+                    scancode: Some(code),
+                    repeat,
+                    ..
+                } => {
+                    if let Some(key_index) = self.input.key_down(code, repeat) {
+                        emulation.send(Command::Key(Key::try_from(key_index)?, true))?;
                     }
                 }
                 Event::KeyUp {
-                    scancode: Some(ref code),
+                    scancode: Some(code),
+                    ..
+                } => {
+                    if let Some(key_index) = self.input.key_up(code) {
+                        emulation.send(Command::Key(Key::try_from(key_index)?, false))?;
+                    }
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    if let Some(key_index) = self.input.button_down(which, button) {
+                        emulation.send(Command::Key(Key::try_from(key_index)?, true))?;
+                    }
+                }
+                Event::ControllerButtonUp { which, button, .. } => {
+                    if let Some(key_index) = self.input.button_up(which, button) {
+                        emulation.send(Command::Key(Key::try_from(key_index)?, false))?;
+                    }
+                }
+                Event::ControllerDeviceAdded { which, .. } => {
+                    // a controller that can't be opened is left out
+                    if let Err(err) = gamepads.add(which) {
+                        self.notify(format!("Could not open game controller: {}", err));
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    gamepads.remove(which);
+                    for key_index in self.input.release_controller(which) {
+                        emulation.send(Command::Key(Key::try_from(key_index)?, false))?;
+                    }
+                }
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
                     ..
                 } => {
-                    if let Some(key_index) = self.keymap.get(code) {
-                        self.cpu.set_key_status(*key_index as usize, false)?;
+                    for key_index in self.input.release_all() {
+                        emulation.send(Command::Key(Key::try_from(key_index)?, false))?;
+                    }
+                    // Tab is released elsewhere when switching windows
+                    if self.is_fast_forward {
+                        self.set_fast_forward(false, emulation)?;
                     }
                 }
                 _ => {}
@@ -136,6 +890,7 @@ impl<'a> VM<'a> {
     }
 }
 
-fn to_sdl_err(err: String) -> Error {
-    Error::SystemError(err)
+// the time taken by a number of 60 Hz timer ticks
+fn ticks_duration(ticks: u32) -> Duration {
+    Duration::from_secs_f64(ticks as f64 / TIMER_HZ)
 }
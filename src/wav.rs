@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const HEADER_SIZE: u32 = 44;
+const BYTES_PER_SAMPLE: u16 = 2;
+
+/// Writes mono 16-bit PCM WAV files. The sizes in the header are written on
+/// `finish`, or when the writer is dropped if the recording is cut short.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    // size of the samples written, in bytes
+    data_size: u32,
+    is_finished: bool,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create(path: &Path, sample_rate: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, sample_rate: u32) -> io::Result<Self> {
+        let block_align = BYTES_PER_SAMPLE;
+        let byte_rate = sample_rate * block_align as u32;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?; // PCM
+        writer.write_all(&1u16.to_le_bytes())?; // mono
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.flush()?;
+
+        Ok(Self {
+            writer,
+            data_size: 0,
+            is_finished: false,
+        })
+    }
+
+    /// Appends samples between -1.0 and 1.0
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.data_size += samples.len() as u32 * BYTES_PER_SAMPLE as u32;
+        Ok(())
+    }
+
+    /// Writes the sizes of the samples to the header, and flushes the file
+    pub fn finish(mut self) -> io::Result<()> {
+        self.is_finished = true;
+        self.write_sizes()
+    }
+
+    fn write_sizes(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        if !self.is_finished {
+            // there's no one to report errors to
+            let _ = self.write_sizes();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn i16_at(bytes: &[u8], offset: usize) -> i16 {
+        i16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_new_writes_the_header() {
+        let mut bytes = Cursor::new(vec![]);
+        WavWriter::new(&mut bytes, 44_100)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let bytes = bytes.into_inner();
+
+        assert_eq!(bytes.len(), HEADER_SIZE as usize);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&bytes, 24), 44_100);
+        assert_eq!(u32_at(&bytes, 28), 88_200);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40), 0);
+    }
+
+    #[test]
+    fn test_write_appends_samples_and_finish_writes_the_sizes() {
+        let mut bytes = Cursor::new(vec![]);
+        let mut wav = WavWriter::new(&mut bytes, 44_100).unwrap();
+
+        wav.write(&[0.0, 1.0]).unwrap();
+        wav.write(&[-1.0, 2.0, 0.5]).unwrap();
+        wav.finish().unwrap();
+        let bytes = bytes.into_inner();
+
+        assert_eq!(bytes.len(), HEADER_SIZE as usize + 10);
+        assert_eq!(u32_at(&bytes, 4), HEADER_SIZE - 8 + 10);
+        assert_eq!(u32_at(&bytes, 40), 10);
+        let samples: Vec<i16> = (0..5).map(|i| i16_at(&bytes, 44 + i * 2)).collect();
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX, 16384]);
+    }
+
+    #[test]
+    fn test_drop_writes_the_sizes() {
+        let mut bytes = Cursor::new(vec![]);
+        let mut wav = WavWriter::new(&mut bytes, 44_100).unwrap();
+
+        wav.write(&[0.5; 3]).unwrap();
+        drop(wav);
+        let bytes = bytes.into_inner();
+
+        assert_eq!(u32_at(&bytes, 4), HEADER_SIZE - 8 + 6);
+        assert_eq!(u32_at(&bytes, 40), 6);
+    }
+}